@@ -0,0 +1,57 @@
+use core::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+static ALLOCATIONS: AtomicUsize = ATOMIC_USIZE_INIT;
+static BYTES: AtomicUsize = ATOMIC_USIZE_INIT;
+static LIVE: AtomicUsize = ATOMIC_USIZE_INIT;
+static PEAK: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Totals since the program started.
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    /// Allocations and reallocations
+    pub allocations: usize,
+    /// Bytes requested by all allocations and reallocations
+    pub bytes: usize,
+    /// Bytes currently allocated
+    pub live: usize,
+    /// Highest `live` since the last `reset_peak`
+    pub peak: usize,
+}
+
+pub fn stats() -> Stats {
+    Stats {
+        allocations: ALLOCATIONS.load(Ordering::SeqCst),
+        bytes: BYTES.load(Ordering::SeqCst),
+        live: LIVE.load(Ordering::SeqCst),
+        peak: PEAK.load(Ordering::SeqCst),
+    }
+}
+
+/// Restarts the peak tracking from the current live bytes.
+pub fn reset_peak() {
+    PEAK.store(LIVE.load(Ordering::SeqCst), Ordering::SeqCst);
+}
+
+/// Records a block of `old_size` bytes becoming `size` bytes. Allocations have an
+/// `old_size` of 0 and deallocations a `size` of 0, which is not counted as an allocation.
+pub fn count(old_size: usize, size: usize) {
+    if size == 0 {
+        LIVE.fetch_sub(old_size, Ordering::SeqCst);
+        return;
+    }
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    BYTES.fetch_add(size, Ordering::Relaxed);
+    let live = if size >= old_size {
+        LIVE.fetch_add(size - old_size, Ordering::SeqCst) + size - old_size
+    } else {
+        LIVE.fetch_sub(old_size - size, Ordering::SeqCst) - (old_size - size)
+    };
+    let mut peak = PEAK.load(Ordering::SeqCst);
+    while live > peak {
+        let previous = PEAK.compare_and_swap(peak, live, Ordering::SeqCst);
+        if previous == peak {
+            break;
+        }
+        peak = previous;
+    }
+}
@@ -0,0 +1,72 @@
+//! An allocator crate replacing jemalloc in the program it is linked into. Memory comes
+//! from the system malloc, like `alloc_system`, or with the `slab` feature small blocks
+//! come from thread caching slabs. With the `count` feature every allocation is counted.
+
+#![feature(allocator, thread_local)]
+// Tests run on the default allocator with std, calling the backends directly
+#![cfg_attr(not(test), allocator)]
+#![no_std]
+
+#[cfg(test)]
+#[macro_use]
+extern crate std;
+
+mod system;
+#[cfg(feature = "slab")]
+mod slab;
+#[cfg(feature = "count")]
+mod count;
+
+#[cfg(feature = "count")]
+pub use count::{Stats, stats, reset_peak};
+
+#[cfg(not(feature = "slab"))]
+use system as backend;
+#[cfg(feature = "slab")]
+use slab as backend;
+
+#[cfg(feature = "count")]
+use count::count;
+
+#[cfg(not(feature = "count"))]
+#[inline(always)]
+fn count(_old_size: usize, _size: usize) {}
+
+/// Name of the allocator, for the benchmark reports.
+pub fn name() -> &'static str {
+    if cfg!(feature = "slab") { "slab" } else { "system" }
+}
+
+#[cfg_attr(not(test), no_mangle)]
+pub extern fn __rust_allocate(size: usize, align: usize) -> *mut u8 {
+    let ptr = unsafe { backend::allocate(size, align) };
+    if !ptr.is_null() {
+        count(0, size);
+    }
+    ptr
+}
+
+#[cfg_attr(not(test), no_mangle)]
+pub extern fn __rust_deallocate(ptr: *mut u8, old_size: usize, align: usize) {
+    unsafe { backend::deallocate(ptr, old_size, align) };
+    count(old_size, 0);
+}
+
+#[cfg_attr(not(test), no_mangle)]
+pub extern fn __rust_reallocate(ptr: *mut u8, old_size: usize, size: usize, align: usize) -> *mut u8 {
+    let new_ptr = unsafe { backend::reallocate(ptr, old_size, size, align) };
+    if !new_ptr.is_null() {
+        count(old_size, size);
+    }
+    new_ptr
+}
+
+#[cfg_attr(not(test), no_mangle)]
+pub extern fn __rust_reallocate_inplace(_ptr: *mut u8, old_size: usize, _size: usize, _align: usize) -> usize {
+    old_size
+}
+
+#[cfg_attr(not(test), no_mangle)]
+pub extern fn __rust_usable_size(size: usize, _align: usize) -> usize {
+    size
+}
@@ -0,0 +1,269 @@
+//! Blocks of up to `MAX_SIZE` bytes are served from per thread free lists, one per power
+//! of two size class, refilled by bumping through chunks taken from malloc. Freed blocks
+//! go to the free list of the freeing thread. When a thread exits, its free lists and the
+//! rest of its chunk are handed to global lists, which the next thread whose own list
+//! runs empty takes over whole. Chunks are never given back to malloc, so memory only
+//! grows to the highest use of every size class, also with pools created per iteration.
+
+use core::{cmp, ptr};
+use core::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+use system::{self, MIN_ALIGN};
+
+const MIN_SHIFT: usize = 4;
+const CLASSES: usize = 6;
+/// Largest block served from the slabs, larger ones go to malloc
+const MAX_SIZE: usize = 1 << (MIN_SHIFT + CLASSES - 1);
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[thread_local]
+static mut FREE: [*mut u8; CLASSES] = [0 as *mut u8; CLASSES];
+#[thread_local]
+static mut BUMP: *mut u8 = 0 as *mut u8;
+#[thread_local]
+static mut BUMP_END: *mut u8 = 0 as *mut u8;
+/// Whether `release` runs when this thread exits
+#[thread_local]
+static mut REGISTERED: bool = false;
+
+/// Heads of the free lists left by exited threads, one per size class
+static ORPHANS: [AtomicUsize; CLASSES] = [
+    ATOMIC_USIZE_INIT, ATOMIC_USIZE_INIT, ATOMIC_USIZE_INIT,
+    ATOMIC_USIZE_INIT, ATOMIC_USIZE_INIT, ATOMIC_USIZE_INIT,
+];
+
+fn class(size: usize, align: usize) -> Option<usize> {
+    if size > MAX_SIZE || align > MIN_ALIGN {
+        return None;
+    }
+    let mut class = 0;
+    while (1 << (MIN_SHIFT + class)) < size {
+        class += 1;
+    }
+    Some(class)
+}
+
+unsafe fn allocate_block(class: usize) -> *mut u8 {
+    let mut head = FREE[class];
+    if head.is_null() {
+        head = ORPHANS[class].swap(0, Ordering::Acquire) as *mut u8;
+    }
+    if !head.is_null() {
+        // Free blocks store the next free block in their first word
+        FREE[class] = *(head as *mut *mut u8);
+        return head;
+    }
+    let size = 1 << (MIN_SHIFT + class);
+    if (BUMP_END as usize) - (BUMP as usize) < size {
+        register();
+        let chunk = system::allocate(CHUNK_SIZE, MIN_ALIGN);
+        if chunk.is_null() {
+            return ptr::null_mut();
+        }
+        BUMP = chunk;
+        BUMP_END = chunk.offset(CHUNK_SIZE as isize);
+    }
+    // Chunks and all block sizes are multiples of MIN_ALIGN, so every block is aligned
+    let block = BUMP;
+    BUMP = BUMP.offset(size as isize);
+    block
+}
+
+pub unsafe fn allocate(size: usize, align: usize) -> *mut u8 {
+    match class(size, align) {
+        Some(class) => allocate_block(class),
+        None => system::allocate(size, align),
+    }
+}
+
+pub unsafe fn deallocate(ptr: *mut u8, old_size: usize, align: usize) {
+    match class(old_size, align) {
+        Some(class) => {
+            register();
+            *(ptr as *mut *mut u8) = FREE[class];
+            FREE[class] = ptr;
+        }
+        None => system::deallocate(ptr, old_size, align),
+    }
+}
+
+pub unsafe fn reallocate(ptr: *mut u8, old_size: usize, size: usize, align: usize) -> *mut u8 {
+    let (old_class, new_class) = (class(old_size, align), class(size, align));
+    if old_class.is_none() && new_class.is_none() {
+        return system::reallocate(ptr, old_size, size, align);
+    }
+    if old_class.is_some() && old_class == new_class {
+        return ptr;
+    }
+    let new_ptr = allocate(size, align);
+    if !new_ptr.is_null() {
+        ptr::copy_nonoverlapping(ptr, new_ptr, cmp::min(old_size, size));
+        deallocate(ptr, old_size, align);
+    }
+    new_ptr
+}
+
+unsafe fn register() {
+    if !REGISTERED {
+        REGISTERED = true;
+        exit::register(release);
+    }
+}
+
+/// Cuts what is left of the chunk into blocks and hands all free blocks of this thread
+/// to `ORPHANS`.
+unsafe fn release() {
+    for class in (0..CLASSES).rev() {
+        let size = 1 << (MIN_SHIFT + class);
+        while (BUMP_END as usize) - (BUMP as usize) >= size {
+            *(BUMP as *mut *mut u8) = FREE[class];
+            FREE[class] = BUMP;
+            BUMP = BUMP.offset(size as isize);
+        }
+    }
+    for class in 0..CLASSES {
+        let head = FREE[class];
+        if head.is_null() {
+            continue;
+        }
+        let mut tail = head;
+        while !(*(tail as *mut *mut u8)).is_null() {
+            tail = *(tail as *mut *mut u8);
+        }
+        let mut orphans = ORPHANS[class].load(Ordering::Relaxed);
+        loop {
+            *(tail as *mut *mut u8) = orphans as *mut u8;
+            let previous = ORPHANS[class].compare_and_swap(orphans, head as usize, Ordering::Release);
+            if previous == orphans {
+                break;
+            }
+            orphans = previous;
+        }
+        FREE[class] = ptr::null_mut();
+    }
+    // Anything this thread frees or bumps while exiting registers it again
+    REGISTERED = false;
+}
+
+/// Calls a function when the current thread exits, through a pthread key destructor.
+mod exit {
+    use core::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+
+    #[cfg(target_os = "linux")]
+    type Key = u32;
+    #[cfg(not(target_os = "linux"))]
+    type Key = usize;
+
+    extern {
+        fn pthread_key_create(key: *mut Key, destructor: Option<unsafe extern fn(*mut u8)>) -> i32;
+        fn pthread_setspecific(key: Key, value: *const u8) -> i32;
+    }
+
+    const UNINITIALIZED: usize = 0;
+    const INITIALIZING: usize = 1;
+    const READY: usize = 2;
+
+    static STATE: AtomicUsize = ATOMIC_USIZE_INIT;
+    static mut KEY: Key = 0;
+    static mut ON_EXIT: Option<unsafe fn()> = None;
+
+    unsafe extern fn destructor(_value: *mut u8) {
+        if let Some(on_exit) = ON_EXIT {
+            on_exit();
+        }
+    }
+
+    /// Runs `on_exit` when the calling thread exits. Every caller passes the same function.
+    pub unsafe fn register(on_exit: unsafe fn()) {
+        if STATE.compare_and_swap(UNINITIALIZED, INITIALIZING, Ordering::SeqCst) == UNINITIALIZED {
+            ON_EXIT = Some(on_exit);
+            pthread_key_create(&mut KEY, Some(destructor));
+            STATE.store(READY, Ordering::SeqCst);
+        }
+        while STATE.load(Ordering::SeqCst) != READY {}
+        // pthread only calls the destructor for threads with a non-null value
+        pthread_setspecific(KEY, 1 as *const u8);
+    }
+}
+
+#[test]
+fn test_class() {
+    assert_eq!(Some(0), class(0, 1));
+    assert_eq!(Some(0), class(16, 8));
+    assert_eq!(Some(1), class(17, 8));
+    assert_eq!(Some(2), class(64, MIN_ALIGN));
+    assert_eq!(Some(CLASSES - 1), class(MAX_SIZE, 8));
+    assert_eq!(None, class(MAX_SIZE + 1, 8));
+    assert_eq!(None, class(16, MIN_ALIGN * 2));
+}
+
+#[test]
+fn test_allocate_deallocate() {
+    unsafe {
+        let a = allocate(24, 8);
+        let b = allocate(24, 8);
+        assert!(!a.is_null() && !b.is_null() && a != b);
+        assert_eq!(0, a as usize % MIN_ALIGN);
+        assert!((b as usize).wrapping_sub(a as usize) >= 32);
+        // Freed blocks are reused by the next allocation of the same class
+        deallocate(a, 24, 8);
+        assert_eq!(a, allocate(32, 8));
+        assert!(allocate(16, 8) != a);
+
+        let large = allocate(MAX_SIZE + 1, 8);
+        assert!(!large.is_null());
+        *large.offset(MAX_SIZE as isize) = 1;
+        deallocate(large, MAX_SIZE + 1, 8);
+        deallocate(a, 32, 8);
+        deallocate(b, 24, 8);
+    }
+}
+
+#[test]
+fn test_reallocate() {
+    unsafe {
+        let a = allocate(20, 8);
+        for i in 0..20 {
+            *a.offset(i) = i as u8;
+        }
+        // Same class stays in place
+        assert_eq!(a, reallocate(a, 20, 30, 8));
+        // Another class moves and frees the old block
+        let b = reallocate(a, 30, 100, 8);
+        assert!(b != a);
+        assert_eq!(a, allocate(30, 8));
+        // Out of the slabs, within malloc and back into them
+        let c = reallocate(b, 100, 1000, 8);
+        let d = reallocate(c, 1000, 4000, 8);
+        let e = reallocate(d, 4000, 10, 8);
+        for i in 0..10 {
+            assert_eq!(i as u8, *e.offset(i));
+        }
+        deallocate(e, 10, 8);
+        deallocate(a, 30, 8);
+    }
+}
+
+#[test]
+fn test_thread_exit() {
+    use std::thread;
+    use std::vec::Vec;
+
+    let freed = thread::spawn(|| unsafe {
+        let blocks: Vec<usize> = (0..4).map(|_| allocate(300, 8) as usize).collect();
+        for &b in blocks.iter() {
+            deallocate(b as *mut u8, 300, 8);
+        }
+        blocks
+    }).join().unwrap();
+    unsafe {
+        // Other tests' threads may have left blocks too, so take as many as could be orphaned
+        let blocks: Vec<*mut u8> = (0..8 * CHUNK_SIZE / MAX_SIZE).map(|_| allocate(300, 8)).collect();
+        for &b in freed.iter() {
+            assert!(blocks.contains(&(b as *mut u8)));
+        }
+        for &b in blocks.iter() {
+            deallocate(b, 300, 8);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use core::{cmp, ptr};
+
+/// Alignment malloc guarantees on its own.
+#[cfg(target_pointer_width = "64")]
+pub const MIN_ALIGN: usize = 16;
+#[cfg(target_pointer_width = "32")]
+pub const MIN_ALIGN: usize = 8;
+
+extern {
+    fn malloc(size: usize) -> *mut u8;
+    fn realloc(ptr: *mut u8, size: usize) -> *mut u8;
+    fn free(ptr: *mut u8);
+    fn posix_memalign(ptr: *mut *mut u8, align: usize, size: usize) -> i32;
+}
+
+pub unsafe fn allocate(size: usize, align: usize) -> *mut u8 {
+    if align <= MIN_ALIGN {
+        malloc(size)
+    } else {
+        let mut out = ptr::null_mut();
+        if posix_memalign(&mut out, align, size) != 0 { ptr::null_mut() } else { out }
+    }
+}
+
+pub unsafe fn deallocate(ptr: *mut u8, _old_size: usize, _align: usize) {
+    free(ptr)
+}
+
+pub unsafe fn reallocate(ptr: *mut u8, old_size: usize, size: usize, align: usize) -> *mut u8 {
+    if align <= MIN_ALIGN {
+        realloc(ptr, size)
+    } else {
+        let new_ptr = allocate(size, align);
+        if !new_ptr.is_null() {
+            ptr::copy_nonoverlapping(ptr, new_ptr, cmp::min(old_size, size));
+            free(ptr);
+        }
+        new_ptr
+    }
+}
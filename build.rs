@@ -0,0 +1,53 @@
+//! Records the compiler and the git state the binary is built from, for the run manifest.
+//! Asking at run time would describe whatever is installed and checked out then.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let rustc = env::var("RUSTC").unwrap_or("rustc".to_string());
+    let dirty = command_output("git", &["status", "--porcelain"]).map(|s| (!s.is_empty()).to_string());
+    set_env("BUILD_RUSTC", command_output(&rustc, &["--version"]));
+    set_env("BUILD_COMMIT", command_output("git", &["rev-parse", "HEAD"]));
+    set_env("BUILD_DIRTY", dirty);
+
+    // Commits and edits change the values above, so rebuild on either
+    let mut watched = vec![".git/HEAD".to_string(), ".git/index".to_string(), "Cargo.toml".to_string(), "build.rs".to_string()];
+    let mut head = String::new();
+    if File::open(".git/HEAD").and_then(|mut f| f.read_to_string(&mut head)).is_ok() && head.starts_with("ref: ") {
+        watched.push(format!(".git/{}", head["ref: ".len()..].trim()));
+    }
+    collect_files(Path::new("src"), &mut watched);
+    for path in watched {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+}
+
+/// Sets `name` for `env!`, empty if the value is unknown.
+fn set_env(name: &str, value: Option<String>) {
+    println!("cargo:rustc-env={}={}", name, value.unwrap_or(String::new()));
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program).args(args).output().ok()
+        .and_then(|output| if output.status.success() { String::from_utf8(output.stdout).ok() } else { None })
+        .map(|s| s.trim().to_string())
+}
+
+/// `dir` and everything below it.
+fn collect_files(dir: &Path, files: &mut Vec<String>) {
+    files.push(dir.to_string_lossy().into_owned());
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.is_dir() {
+                collect_files(&path, files);
+            } else {
+                files.push(path.to_string_lossy().into_owned());
+            }
+        }
+    }
+}
@@ -13,7 +13,12 @@ cd forkjoin-benchmarking
 
 cargo build --release --features linux-affinity
 
-rm -rf .criterion/ && cargo run --release --features linux-affinity -- fib qsort mergesort sumtree_unbalanced nqueens_reduce nqueens_search --threads 1 2 4 8 12 16 20 24 28 32 --fib 42 --sort 10000000 --sumtree 23 --nqueens 12 -s 20 | tee output.txt && mv output.txt .criterion/
+rm -rf .criterion/ && cargo run --release --features linux-affinity -- fib qsort mergesort sumtree_unbalanced nqueens_reduce nqueens_search --threads 1 2 4..=max:4 --fib 42 --sort 10000000 --sumtree 23 --nqueens 12 -s 20 | tee output.txt && mv output.txt .criterion/
 
 tar -czf $HOME/criterion-data.tar.gz .criterion/*
 
+
+# Compare allocators, one build each, jemalloc being the default
+#cargo run --release --features linux-affinity -- fib mergesort sumtree_unbalanced nqueens_reduce --threads 1 2 4..=max:4 -s 20 --save-baseline jemalloc
+#for alloc in system-alloc slab-alloc; do cargo run --release --features "linux-affinity $alloc" -- fib mergesort sumtree_unbalanced nqueens_reduce --threads 1 2 4..=max:4 -s 20 --save-baseline $alloc; done
+#cargo run --release -- fib mergesort sumtree_unbalanced nqueens_reduce --allocator-report jemalloc system-alloc slab-alloc
@@ -0,0 +1,94 @@
+//! Allocation counts per job from the counting allocator in alloc-shim. Only compiled in
+//! with the `count-allocations` feature, otherwise `Tracker::start` returns None.
+//!
+//! The allocator counts all threads, so pool workers are included, but so is anything
+//! else allocating concurrently.
+
+/// The allocator the harness is built against.
+#[cfg(any(feature = "system-alloc", feature = "slab-alloc", feature = "count-allocations"))]
+pub fn allocator() -> &'static str {
+    ::alloc_shim::name()
+}
+
+#[cfg(not(any(feature = "system-alloc", feature = "slab-alloc", feature = "count-allocations")))]
+pub fn allocator() -> &'static str {
+    "jemalloc"
+}
+
+/// What one job allocated.
+#[derive(Debug, Clone, Copy)]
+pub struct JobAllocs {
+    pub allocations: u64,
+    pub bytes: u64,
+    /// Highest live bytes during the job, above the live bytes when it started
+    pub peak: u64,
+}
+
+/// Allocations and bytes averaged over all jobs, the peak is the highest of any job.
+#[derive(Debug, Clone, Copy)]
+pub struct Allocs {
+    pub allocations: f64,
+    pub bytes: f64,
+    pub peak: u64,
+}
+
+#[cfg(feature = "count-allocations")]
+pub struct Tracker {
+    start: ::alloc_shim::Stats,
+}
+
+#[cfg(feature = "count-allocations")]
+impl Tracker {
+    pub fn start() -> Option<Tracker> {
+        ::alloc_shim::reset_peak();
+        Some(Tracker { start: ::alloc_shim::stats() })
+    }
+
+    pub fn finish(self) -> JobAllocs {
+        let end = ::alloc_shim::stats();
+        JobAllocs {
+            allocations: (end.allocations - self.start.allocations) as u64,
+            bytes: (end.bytes - self.start.bytes) as u64,
+            peak: end.peak.saturating_sub(self.start.live) as u64,
+        }
+    }
+}
+
+#[cfg(not(feature = "count-allocations"))]
+pub struct Tracker;
+
+#[cfg(not(feature = "count-allocations"))]
+impl Tracker {
+    pub fn start() -> Option<Tracker> {
+        None
+    }
+
+    pub fn finish(self) -> JobAllocs {
+        JobAllocs { allocations: 0, bytes: 0, peak: 0 }
+    }
+}
+
+pub fn average(jobs: &[JobAllocs]) -> Option<Allocs> {
+    if jobs.is_empty() {
+        return None;
+    }
+    let n = jobs.len() as f64;
+    Some(Allocs {
+        allocations: jobs.iter().fold(0.0, |acc, j| acc + j.allocations as f64) / n,
+        bytes: jobs.iter().fold(0.0, |acc, j| acc + j.bytes as f64) / n,
+        peak: jobs.iter().map(|j| j.peak).max().unwrap(),
+    })
+}
+
+#[test]
+fn test_average() {
+    let jobs = [
+        JobAllocs { allocations: 2, bytes: 100, peak: 64 },
+        JobAllocs { allocations: 4, bytes: 300, peak: 32 },
+    ];
+    let allocs = average(&jobs[..]).unwrap();
+    assert_eq!(3.0, allocs.allocations);
+    assert_eq!(200.0, allocs.bytes);
+    assert_eq!(64, allocs.peak);
+    assert!(average(&[]).is_none());
+}
@@ -0,0 +1,264 @@
+use thread_scoped;
+
+use std::collections::VecDeque;
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+
+use pin;
+
+/// What runs the parallel implementation of a workload.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// ForkJoin's work stealing `ForkPool`
+    ForkJoin,
+    /// A new `std::thread` per forked task down to a depth cut-off, while fewer than the
+    /// thread count are running
+    Spawn,
+    /// `NaivePool`, all workers sharing one locked queue
+    NaivePool,
+}
+
+impl Backend {
+    pub fn parse(name: &str) -> Backend {
+        match name {
+            "forkjoin" => Backend::ForkJoin,
+            "spawn" => Backend::Spawn,
+            "naivepool" => Backend::NaivePool,
+            other => panic!("Invalid backend: {}", other),
+        }
+    }
+}
+
+/// Fork-join primitive the non-ForkJoin backends implement. Workloads written against
+/// this recurse by calling `join_all` with their subtasks and `depth + 1` for the children.
+pub trait Join: Sync {
+    /// Runs all `tasks`, possibly in parallel, and returns their results in order.
+    /// `depth` is the recursion depth of the calling task, 0 for the root.
+    fn join_all<R, F>(&self, depth: usize, tasks: Vec<F>) -> Vec<R> where
+        R: Send,
+        F: FnOnce() -> R + Send;
+}
+
+/// Runs every task but the first on a freshly spawned thread, for calls from tasks above
+/// `max_depth` and as long as fewer than `max_spawned` spawned threads are running. The
+/// other tasks run on the caller.
+pub struct SpawnJoin {
+    max_depth: usize,
+    max_spawned: usize,
+    spawned: AtomicUsize,
+    cpus: Vec<usize>,
+}
+
+impl SpawnJoin {
+    /// At most `threads` running threads, counting the one calling `join_all` first.
+    /// Spawning stops at the depth where a binary task tree has `threads` tasks.
+    /// Spawned threads run on `cpus`, or wherever the scheduler puts them if it is empty.
+    pub fn for_threads(threads: usize, cpus: &[usize]) -> SpawnJoin {
+        let mut max_depth = 0;
+        while (1 << max_depth) < threads {
+            max_depth += 1;
+        }
+        SpawnJoin {
+            max_depth: max_depth,
+            max_spawned: if threads > 0 { threads - 1 } else { 0 },
+            spawned: AtomicUsize::new(0),
+            cpus: cpus.to_vec(),
+        }
+    }
+
+    /// Takes one of the threads that may be spawned, false if all of them are running.
+    fn reserve(&self) -> bool {
+        let mut spawned = self.spawned.load(Ordering::SeqCst);
+        while spawned < self.max_spawned {
+            let previous = self.spawned.compare_and_swap(spawned, spawned + 1, Ordering::SeqCst);
+            if previous == spawned {
+                return true;
+            }
+            spawned = previous;
+        }
+        false
+    }
+}
+
+/// A task given to `SpawnJoin::join_all`, either running on its own thread or
+/// waiting to be run by the caller.
+enum Branch<'a, R: Send + 'a, F> {
+    Spawned(thread_scoped::JoinGuard<'a, R>),
+    Inline(F),
+}
+
+impl Join for SpawnJoin {
+    fn join_all<R, F>(&self, depth: usize, tasks: Vec<F>) -> Vec<R> where
+        R: Send,
+        F: FnOnce() -> R + Send
+    {
+        let mut tasks = tasks.into_iter();
+        let first = match tasks.next() {
+            Some(first) => first,
+            None => return vec![],
+        };
+        let (spawned, cpus) = (&self.spawned, &self.cpus[..]);
+        let branches: Vec<_> = tasks.map(|task| {
+            if depth < self.max_depth && self.reserve() {
+                Branch::Spawned(unsafe {
+                    thread_scoped::scoped(move || {
+                        // Would otherwise inherit the CPU of a pinned driver
+                        pin::allow_current(cpus);
+                        let result = task();
+                        spawned.fetch_sub(1, Ordering::SeqCst);
+                        result
+                    })
+                })
+            } else {
+                Branch::Inline(task)
+            }
+        }).collect();
+        let mut results = vec![first()];
+        results.extend(branches.into_iter().map(|branch| match branch {
+            Branch::Spawned(guard) => guard.join(),
+            Branch::Inline(task) => task(),
+        }));
+        results
+    }
+}
+
+type PoolJob = Box<FnMut() + Send>;
+
+/// Identifies the `join_all` call that queued a job.
+type Owner = usize;
+
+unsafe fn erase_lifetime<'a>(job: Box<FnMut() + Send + 'a>) -> PoolJob {
+    mem::transmute(job)
+}
+
+struct PoolQueue {
+    jobs: Mutex<VecDeque<(Owner, PoolJob)>>,
+    available: Condvar,
+    shutdown: AtomicBool,
+}
+
+/// The simplest possible thread pool. All workers take jobs from one shared,
+/// mutex protected queue and there is no stealing. A thread waiting in `join_all`
+/// runs those of its own jobs no worker has taken yet, so recursive joins don't
+/// deadlock. It never runs other jobs, which could block it long after its own are done.
+/// The calling thread counts as one of the `threads`, the workers run on `cpus` unless
+/// it is empty.
+pub struct NaivePool {
+    queue: Arc<PoolQueue>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl NaivePool {
+    pub fn new(threads: usize, cpus: &[usize]) -> NaivePool {
+        let queue = Arc::new(PoolQueue {
+            jobs: Mutex::new(VecDeque::new()),
+            available: Condvar::new(),
+            shutdown: AtomicBool::new(false),
+        });
+        let workers = (1..threads).map(|_| {
+            let (queue, cpus) = (queue.clone(), cpus.to_vec());
+            thread::spawn(move || {
+                pin::allow_current(&cpus);
+                loop {
+                    let job = {
+                        let mut jobs = queue.jobs.lock().unwrap();
+                        while jobs.is_empty() && !queue.shutdown.load(Ordering::SeqCst) {
+                            jobs = queue.available.wait(jobs).unwrap();
+                        }
+                        let job = jobs.pop_front();
+                        job
+                    };
+                    match job {
+                        Some((_, mut job)) => job(),
+                        None => return,
+                    }
+                }
+            })
+        }).collect();
+        NaivePool {
+            queue: queue,
+            workers: workers,
+        }
+    }
+}
+
+impl Join for NaivePool {
+    fn join_all<R, F>(&self, _depth: usize, tasks: Vec<F>) -> Vec<R> where
+        R: Send,
+        F: FnOnce() -> R + Send
+    {
+        let slots: Vec<Mutex<Option<R>>> = tasks.iter().map(|_| Mutex::new(None)).collect();
+        let remaining = AtomicUsize::new(tasks.len());
+        let owner = &remaining as *const AtomicUsize as Owner;
+        let mut tasks = tasks.into_iter();
+        let first = match tasks.next() {
+            Some(first) => first,
+            None => return vec![],
+        };
+
+        {
+            let mut jobs = self.queue.jobs.lock().unwrap();
+            for (slot, task) in slots[1..].iter().zip(tasks) {
+                let remaining = &remaining;
+                let mut task = Some(task);
+                let job = Box::new(move || {
+                    let result = (task.take().unwrap())();
+                    *slot.lock().unwrap() = Some(result);
+                    remaining.fetch_sub(1, Ordering::SeqCst);
+                });
+                // Only borrows from this stack frame, which is not left until the job has run
+                jobs.push_back((owner, unsafe { erase_lifetime(job) }));
+            }
+            self.queue.available.notify_all();
+        }
+
+        *slots[0].lock().unwrap() = Some(first());
+        remaining.fetch_sub(1, Ordering::SeqCst);
+
+        while remaining.load(Ordering::SeqCst) > 0 {
+            let job = {
+                let mut jobs = self.queue.jobs.lock().unwrap();
+                let own = jobs.iter().position(|&(queued_by, _)| queued_by == owner);
+                own.and_then(|i| jobs.remove(i))
+            };
+            match job {
+                Some((_, mut job)) => job(),
+                None => thread::yield_now(),
+            }
+        }
+        slots.into_iter().map(|slot| slot.into_inner().unwrap().unwrap()).collect()
+    }
+}
+
+impl Drop for NaivePool {
+    fn drop(&mut self) {
+        self.queue.shutdown.store(true, Ordering::SeqCst);
+        {
+            let _jobs = self.queue.jobs.lock().unwrap();
+            self.queue.available.notify_all();
+        }
+        for worker in self.workers.drain(..) {
+            worker.join().unwrap();
+        }
+    }
+}
+
+#[test]
+fn test_join_all() {
+    fn sum<J: Join>(join: &J, depth: usize, from: usize, to: usize) -> usize {
+        if to - from <= 4 {
+            (from..to).fold(0, |acc, x| acc + x)
+        } else {
+            let mid = (from + to) / 2;
+            let halves = vec![(from, mid), (mid, to)];
+            join.join_all(depth, halves.into_iter().map(|(f, t)| move || sum(join, depth + 1, f, t)).collect())
+                .iter().fold(0, |acc, &x| acc + x)
+        }
+    }
+    assert_eq!(499500, sum(&SpawnJoin::for_threads(4, &[]), 0, 0, 1000));
+    assert_eq!(2, SpawnJoin::for_threads(4, &[]).max_depth);
+    assert_eq!(0, SpawnJoin::for_threads(1, &[]).max_depth);
+    assert_eq!(499500, sum(&NaivePool::new(4, &[]), 0, 0, 1000));
+}
@@ -0,0 +1,203 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use allocs::Allocs;
+use manifest::Manifest;
+use threadstats::PoolStats;
+use stats;
+
+/// Significance level for flagging a change.
+pub const ALPHA: f64 = 0.05;
+/// Changes of the median smaller than this are never flagged, however significant.
+pub const MIN_CHANGE: f64 = 0.02;
+
+/// The job times of one implementation of one workload, in ns.
+pub struct Row {
+    /// Workload and argument, as in the criterion group name, e.g. "fib_31"
+    pub workload: String,
+    /// e.g. "T4"
+    pub implementation: String,
+    pub samples: Vec<u64>,
+    /// Average count per job of every `perf::EVENTS`, not stored in baselines
+    pub counters: Vec<Option<f64>>,
+    /// Allocations per job, not stored in baselines either
+    pub allocs: Option<Allocs>,
+    /// Saved to threadstats.tsv next to the samples, but not loaded
+    pub threadstats: Option<PoolStats>,
+    /// Thread id and CPU of every pinned worker, saved to pinning.tsv but not loaded
+    pub pinned: Vec<(i32, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    Regression,
+    Improvement,
+    Unchanged,
+}
+
+pub struct Comparison<'a> {
+    pub workload: &'a str,
+    pub implementation: &'a str,
+    pub old_median: u64,
+    pub new_median: u64,
+    /// Two sided p-value of the Mann-Whitney U test
+    pub p_value: f64,
+    pub verdict: Verdict,
+}
+
+impl<'a> Comparison<'a> {
+    pub fn change(&self) -> f64 {
+        self.new_median as f64 / self.old_median as f64 - 1.0
+    }
+}
+
+fn baseline_dir(name: &str) -> PathBuf {
+    PathBuf::from(".criterion").join("baselines").join(name)
+}
+
+/// Stores the rows as .criterion/baselines/`name`/samples.tsv, one row per line with
+/// the workload, implementation and comma separated samples separated by tabs. The
+/// manifest of the run is saved next to it, worker statistics per job in threadstats.tsv
+/// if built with `threadstats`, and the CPU of every worker thread in pinning.tsv if pinned.
+pub fn save(name: &str, rows: &[Row], manifest: &Manifest) -> io::Result<()> {
+    let dir = baseline_dir(name);
+    try!(manifest.save(&dir));
+    let mut file = try!(File::create(dir.join("samples.tsv")));
+    for row in rows {
+        let samples: Vec<String> = row.samples.iter().map(|s| s.to_string()).collect();
+        try!(writeln!(file, "{}\t{}\t{}", row.workload, row.implementation, samples.join(",")));
+    }
+    if rows.iter().any(|r| r.threadstats.is_some()) {
+        let mut file = try!(File::create(dir.join("threadstats.tsv")));
+        try!(writeln!(file, "workload\timplementation\tworkers\ttasks\tsteals\tfailed_steals\tidle_us\tbusiest_share"));
+        for row in rows {
+            if let Some(ref s) = row.threadstats {
+                try!(writeln!(file, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", row.workload, row.implementation, s.workers.len(),
+                    s.tasks(), s.steals(), s.failed_steals(), s.idle_us(), s.busiest_share()));
+            }
+        }
+    }
+    if rows.iter().any(|r| !r.pinned.is_empty()) {
+        let mut file = try!(File::create(dir.join("pinning.tsv")));
+        try!(writeln!(file, "workload\timplementation\ttid\tcpu"));
+        for row in rows {
+            for &(tid, cpu) in row.pinned.iter() {
+                try!(writeln!(file, "{}\t{}\t{}\t{}", row.workload, row.implementation, tid, cpu));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn load(name: &str) -> io::Result<Vec<Row>> {
+    let file = try!(File::open(baseline_dir(name).join("samples.tsv")));
+    let mut rows = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = try!(line);
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 3 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid baseline line: {}", line)));
+        }
+        let samples = fields[2].split(',').filter(|s| !s.is_empty()).map(|s| s.parse::<u64>());
+        rows.push(Row {
+            workload: fields[0].to_string(),
+            implementation: fields[1].to_string(),
+            samples: try!(samples.collect::<Result<Vec<u64>, _>>()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))),
+            counters: vec![],
+            allocs: None,
+            threadstats: None,
+            pinned: vec![],
+        });
+    }
+    Ok(rows)
+}
+
+/// Compares every new row with the old row of the same workload and implementation.
+/// Rows only present in one of them are skipped.
+pub fn compare<'a>(old: &'a [Row], new: &'a [Row]) -> Vec<Comparison<'a>> {
+    new.iter().filter_map(|n| {
+        old.iter().find(|o| o.workload == n.workload && o.implementation == n.implementation).map(|o| {
+            let (old_median, new_median) = (stats::median(&o.samples[..]), stats::median(&n.samples[..]));
+            let p_value = mann_whitney_u(&o.samples[..], &n.samples[..]);
+            let change = new_median as f64 / old_median as f64 - 1.0;
+            let verdict = if p_value >= ALPHA || change.abs() < MIN_CHANGE {
+                Verdict::Unchanged
+            } else if change > 0.0 {
+                Verdict::Regression
+            } else {
+                Verdict::Improvement
+            };
+            Comparison {
+                workload: &n.workload,
+                implementation: &n.implementation,
+                old_median: old_median,
+                new_median: new_median,
+                p_value: p_value,
+                verdict: verdict,
+            }
+        })
+    }).collect()
+}
+
+/// Two sided p-value of the Mann-Whitney U test that `a` and `b` come from the same
+/// distribution, using the normal approximation with tie correction.
+pub fn mann_whitney_u(a: &[u64], b: &[u64]) -> f64 {
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    if a.is_empty() || b.is_empty() {
+        return 1.0;
+    }
+    let mut all: Vec<(u64, usize)> = a.iter().map(|&x| (x, 0)).chain(b.iter().map(|&x| (x, 1))).collect();
+    all.sort();
+
+    // Average ranks over ties, accumulating the tie correction term
+    let mut rank_sum_a = 0.0;
+    let mut ties = 0.0;
+    let mut i = 0;
+    while i < all.len() {
+        let mut j = i;
+        while j < all.len() && all[j].0 == all[i].0 {
+            j += 1;
+        }
+        let rank = (i + j + 1) as f64 / 2.0;
+        rank_sum_a += rank * all[i..j].iter().filter(|&&(_, group)| group == 0).count() as f64;
+        let t = (j - i) as f64;
+        ties += t * t * t - t;
+        i = j;
+    }
+
+    let u = rank_sum_a - n1 * (n1 + 1.0) / 2.0;
+    let n = n1 + n2;
+    let mean = n1 * n2 / 2.0;
+    let variance = n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return 1.0;
+    }
+    // Continuity correction
+    let z = ((u - mean).abs() - 0.5).max(0.0) / variance.sqrt();
+    2.0 * (1.0 - normal_cdf(z))
+}
+
+fn normal_cdf(z: f64) -> f64 {
+    0.5 * (1.0 + erf(z / 2f64.sqrt()))
+}
+
+/// Abramowitz and Stegun 7.1.26, accurate to about 1e-7.
+fn erf(x: f64) -> f64 {
+    let sign = if x < 0.0 { -1.0 } else { 1.0 };
+    let x = x.abs();
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    sign * (1.0 - poly * (-x * x).exp())
+}
+
+#[test]
+fn test_mann_whitney_u() {
+    let a: Vec<u64> = (0..20).collect();
+    assert!(mann_whitney_u(&a[..], &a[..]) > 0.9);
+    let b: Vec<u64> = (100..120).collect();
+    assert!(mann_whitney_u(&a[..], &b[..]) < 0.001);
+    let c: Vec<u64> = (2..22).collect();
+    assert!(mann_whitney_u(&a[..], &c[..]) > ALPHA);
+}
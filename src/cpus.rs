@@ -0,0 +1,180 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+
+/// Number of CPUs this process is allowed to run on, from the affinity mask the
+/// kernel reports in /proc/self/status. Falls back to the processors in /proc/cpuinfo.
+pub fn logical() -> usize {
+    allowed_cpus().map(|cpus| cpus.len())
+        .or_else(|| cpuinfo().map(|procs| procs.len()))
+        .unwrap_or(1)
+}
+
+/// Number of physical cores among the allowed CPUs, counting each distinct
+/// (physical id, core id) pair in /proc/cpuinfo once. Falls back to `logical()`.
+pub fn physical() -> usize {
+    let procs = match cpuinfo() {
+        Some(procs) => procs,
+        None => return logical(),
+    };
+    let allowed = allowed_cpus();
+    let cores: HashSet<(usize, usize)> = procs.iter()
+        .filter(|p| allowed.as_ref().map(|a| a.contains(&p.processor)).unwrap_or(true))
+        .filter_map(|p| p.core)
+        .collect();
+    if cores.is_empty() { logical() } else { cores.len() }
+}
+
+/// Number of CPU packages (sockets) in /proc/cpuinfo.
+pub fn packages() -> usize {
+    let packages: HashSet<usize> = cpuinfo().unwrap_or(vec![]).iter()
+        .filter_map(|p| p.core.map(|(package, _)| package))
+        .collect();
+    if packages.is_empty() { 1 } else { packages.len() }
+}
+
+/// (cpu, package, core) of every allowed CPU. CPUs the kernel reports no topology for
+/// are treated as separate cores of package 0.
+pub fn topology() -> Vec<(usize, usize, usize)> {
+    let procs = cpuinfo().unwrap_or(vec![]);
+    let allowed = allowed_cpus().unwrap_or(procs.iter().map(|p| p.processor).collect());
+    allowed.iter().map(|&cpu| {
+        match procs.iter().find(|p| p.processor == cpu).and_then(|p| p.core) {
+            Some((package, core)) => (cpu, package, core),
+            None => (cpu, 0, cpu),
+        }
+    }).collect()
+}
+
+/// The "model name" of the first processor in /proc/cpuinfo.
+pub fn model() -> Option<String> {
+    read_file("/proc/cpuinfo").and_then(|content| {
+        let model = content.lines()
+            .find(|line| line.starts_with("model name"))
+            .and_then(|line| line.splitn(2, ':').nth(1))
+            .map(|model| model.trim().to_string());
+        model
+    })
+}
+
+/// Expands `--threads` specifications into a sorted list of thread counts. Every
+/// specification is a count, `logical`, `physical`, `pow2` (powers of two up to
+/// `logical`) or a range `a..=b` with an optional step `a..=b:step`, where `b`
+/// can be `max` for `logical`.
+pub fn parse_threads(specs: &[String]) -> Vec<usize> {
+    let max = logical();
+    let mut threads = vec![];
+    for spec in specs {
+        match &spec[..] {
+            "logical" => threads.push(max),
+            "physical" => threads.push(physical()),
+            "pow2" => {
+                let mut t = 1;
+                while t <= max {
+                    threads.push(t);
+                    t *= 2;
+                }
+            },
+            _ => match spec.find("..=") {
+                Some(i) => {
+                    let (range, step) = match spec[i + 3..].find(':') {
+                        Some(j) => (&spec[..i + 3 + j], parse_count(&spec[i + 4 + j..], max)),
+                        None => (&spec[..], 1),
+                    };
+                    let from = parse_count(&range[..i], max);
+                    let to = parse_count(&range[i + 3..], max);
+                    assert!(step > 0, "Invalid thread range step: {}", spec);
+                    let mut t = from;
+                    while t <= to {
+                        threads.push(t);
+                        t += step;
+                    }
+                },
+                None => threads.push(parse_count(spec, max)),
+            },
+        }
+    }
+    threads.sort();
+    threads.dedup();
+    for &t in threads.iter().filter(|&&t| t > max) {
+        println!("Warning: {} threads requested but only {} CPUs are available", t, max);
+    }
+    threads
+}
+
+fn parse_count(s: &str, max: usize) -> usize {
+    match s {
+        "max" => max,
+        _ => s.parse().unwrap_or_else(|_| panic!("Invalid thread count: {}", s)),
+    }
+}
+
+struct Processor {
+    processor: usize,
+    /// (physical id, core id), when the kernel reports them
+    core: Option<(usize, usize)>,
+}
+
+pub fn read_file(path: &str) -> Option<String> {
+    let mut content = String::new();
+    match File::open(path).and_then(|mut f| f.read_to_string(&mut content)) {
+        Ok(_) => Some(content),
+        Err(_) => None,
+    }
+}
+
+fn cpuinfo() -> Option<Vec<Processor>> {
+    let content = match read_file("/proc/cpuinfo") {
+        Some(content) => content,
+        None => return None,
+    };
+    let mut procs = vec![];
+    for block in content.split("\n\n") {
+        let field = |name: &str| -> Option<usize> {
+            block.lines()
+                .find(|line| line.split(':').next().map(|key| key.trim() == name).unwrap_or(false))
+                .and_then(|line| line.split(':').nth(1))
+                .and_then(|value| value.trim().parse().ok())
+        };
+        if let Some(processor) = field("processor") {
+            let core = match (field("physical id"), field("core id")) {
+                (Some(package), Some(core)) => Some((package, core)),
+                _ => None,
+            };
+            procs.push(Processor {
+                processor: processor,
+                core: core,
+            });
+        }
+    }
+    if procs.is_empty() { None } else { Some(procs) }
+}
+
+/// The CPUs in this process' affinity mask, as set by `sched_setaffinity`/taskset.
+pub fn allowed_cpus() -> Option<Vec<usize>> {
+    read_file("/proc/self/status").and_then(|status| {
+        let cpus = status.lines()
+            .find(|line| line.starts_with("Cpus_allowed_list:"))
+            .map(|line| parse_cpu_list(line["Cpus_allowed_list:".len()..].trim()));
+        cpus
+    })
+}
+
+/// Parses the kernel's cpu list format, e.g. "0-3,8,10-11".
+pub fn parse_cpu_list(list: &str) -> Vec<usize> {
+    let mut cpus = vec![];
+    for part in list.split(',').filter(|p| !p.is_empty()) {
+        let mut bounds = part.splitn(2, '-').map(|b| b.trim().parse::<usize>().unwrap());
+        let from = bounds.next().unwrap();
+        let to = bounds.next().unwrap_or(from);
+        cpus.extend(from..to + 1);
+    }
+    cpus
+}
+
+#[test]
+fn test_parse_threads() {
+    assert_eq!(vec![0, 1, 2, 3, 8, 10, 11], parse_cpu_list("0-3,8,10-11"));
+    let specs: Vec<String> = vec!["4".to_string(), "1..=3".to_string(), "2..=10:4".to_string()];
+    assert_eq!(vec![1, 2, 3, 4, 6, 10], parse_threads(&specs[..]));
+}
@@ -4,6 +4,9 @@ use test;
 
 use std::thread::{self, JoinHandle};
 
+use backend::Join;
+use spawnpool::{PoolMode, bench_pool};
+
 pub fn seqfib_spam(b: &mut Bencher, threads: usize, &i: &usize) {
     let expected_result = fib(i);
 
@@ -31,24 +34,18 @@ pub fn seqfib(b: &mut Bencher, &i: &usize) {
     })
 }
 
-pub fn parfib(b: &mut Bencher, threads: usize, &i: &usize) {
-    let forkpool = ForkPool::with_threads(threads);
-    let fibpool = forkpool.init_algorithm(FIB);
-
-    b.iter_with_large_drop(|| {
+pub fn parfib(b: &mut Bencher, threads: usize, mode: PoolMode, &i: &usize) {
+    bench_pool(b, threads, mode, || FIB, || {}, |fibpool, ()| {
         let job = fibpool.schedule(test::black_box(i));
         job.recv().unwrap()
-    })
+    }, |_| {});
 }
 
-pub fn parfib_no_threshold(b: &mut Bencher, threads: usize, &i: &usize) {
-    let forkpool = ForkPool::with_threads(threads);
-    let fibpool = forkpool.init_algorithm(FIB_NO_THRESHOLD);
-
-    b.iter_with_large_drop(|| {
+pub fn parfib_no_threshold(b: &mut Bencher, threads: usize, mode: PoolMode, &i: &usize) {
+    bench_pool(b, threads, mode, || FIB_NO_THRESHOLD, || {}, |fibpool, ()| {
         let job = fibpool.schedule(test::black_box(i));
         job.recv().unwrap()
-    })
+    }, |_| {});
 }
 
 pub fn parfib_once(threads: usize, i: usize) -> usize {
@@ -67,12 +64,27 @@ pub fn parfib_no_threshold_once(threads: usize, i: usize) -> usize {
     job.recv().unwrap()
 }
 
-const FIB: Algorithm<usize, usize> = Algorithm {
+pub fn backend_fib<J: Join>(b: &mut Bencher, join: &J, &i: &usize) {
+    b.iter_with_large_drop(|| {
+        fib_backend(join, 0, test::black_box(i))
+    })
+}
+
+fn fib_backend<J: Join>(join: &J, depth: usize, n: usize) -> usize {
+    if n <= 20 {
+        fib(n)
+    } else {
+        let tasks = vec![n-2, n-1].into_iter().map(|m| move || fib_backend(join, depth + 1, m)).collect();
+        fib_join(&join.join_all(depth, tasks)[..])
+    }
+}
+
+pub const FIB: Algorithm<usize, usize> = Algorithm {
     fun: fib_task,
     style: AlgoStyle::Reduce(ReduceStyle::NoArg(fib_join)),
 };
 
-fn fib_task(n: usize) -> TaskResult<usize, usize> {
+pub fn fib_task(n: usize) -> TaskResult<usize, usize> {
     if n <= 20 {
         TaskResult::Done(fib(n))
     } else {
@@ -93,11 +105,11 @@ fn fib_task_no_threshold(n: usize) -> TaskResult<usize, usize> {
     }
 }
 
-fn fib_join(values: &[usize]) -> usize {
+pub fn fib_join(values: &[usize]) -> usize {
     values.iter().fold(0, |acc, &v| acc + v)
 }
 
-fn fib(n: usize) -> usize {
+pub fn fib(n: usize) -> usize {
     if n < 2 {
         1
     } else {
@@ -8,6 +8,11 @@ extern crate criterion;
 extern crate argparse;
 extern crate forkjoin;
 extern crate time;
+extern crate thread_scoped;
+#[cfg(target_os = "linux")]
+extern crate libc;
+#[cfg(any(feature = "system-alloc", feature = "slab-alloc", feature = "count-allocations"))]
+extern crate alloc_shim;
 
 mod fib;
 mod quicksort;
@@ -16,29 +21,99 @@ mod nqueens;
 mod sumtree;
 mod spawnpool;
 mod sortutils;
+mod sha1;
+mod uts;
+mod spin;
+mod stats;
+mod throughput;
+mod mixed;
+mod nested;
+mod openloop;
+mod backend;
+mod weak;
+mod cpus;
+mod manifest;
+mod measure;
+mod baseline;
+mod plot;
+mod perf;
+mod allocs;
+mod threadstats;
+mod pin;
+mod numa;
+mod trace;
+mod span;
+mod treestats;
 
-use criterion::{Criterion,Fun};
+use criterion::{Bencher,Criterion,Fun};
+use forkjoin::{TaskResult,AlgoStyle,ReduceStyle,Algorithm};
 
-use argparse::{ArgumentParser,Store,List,StoreFalse};
+use argparse::{ArgumentParser,Store,List,StoreFalse,StoreTrue};
+use std::cell::RefCell;
+use std::cmp;
 use std::convert::AsRef;
+use std::fs;
+use std::mem;
+use std::path::Path;
+use std::process;
+use std::slice;
+use std::sync::Arc;
 
 use sortutils::{verify_sorted, create_vec_rnd};
-use fib::{seqfib, parfib, parfib_no_threshold, seqfib_spam, parfib_once, parfib_no_threshold_once};
-use quicksort::{seq_qsort, par_qsort, par_qsort_once};
-use mergesort::{seq_mergesort, par_mergesort, par_mergesort_once};
-use nqueens::{seq_nqueens_reduce, seq_nqueens_search, par_nqueens_reduce, par_nqueens_search, par_nqueens_search_first, par_nqueens_reduce_once};
-use spawnpool::{spawn, spawn_drop, spawn_schedule_drop};
-use sumtree::{gen_unbalanced_tree, gen_list_tree, gen_balanced_tree, seq_sumtree, par_sumtree, par_sumtree_once};
+use fib::{FIB, fib, seqfib, parfib, backend_fib, parfib_no_threshold, seqfib_spam, parfib_once, parfib_no_threshold_once};
+use quicksort::{quicksort_task, quicksort_join, quicksort_seq, seq_qsort, par_qsort, backend_qsort, par_qsort_once};
+use mergesort::{mergesort_task, mergesort_join, mergesort_seq, seq_mergesort, par_mergesort, backend_mergesort, par_mergesort_once};
+use nqueens::{nqueens_reduce, seq_nqueens_reduce, seq_nqueens_search, par_nqueens_reduce, par_nqueens_search, par_nqueens_search_first, par_nqueens_reduce_once, backend_nqueens_reduce, NQUEENS_REDUCE, NQUEENS_SEARCH};
+use spawnpool::{PoolMode, PoolRuns, spawn, spawn_drop, spawn_schedule_drop, lifecycle_once};
+use sumtree::{sum_tree_task, sum_tree_task_cutoff, sum_tree_join, sum_tree_seq, Tree, FlatTree, Layout, Cutoff, gen_unbalanced_tree, gen_list_tree, gen_balanced_tree, set_work, seq_sumtree, par_sumtree, par_sumtree_once, backend_sumtree, par_sumtree_cutoff, par_sumtree_cutoff_once, seq_flat_sumtree, par_flat_sumtree, par_flat_sumtree_once};
+use uts::{uts_params, seq_uts, par_uts, par_uts_once};
+use throughput::{Throughput, fib_throughput, qsort_throughput};
+use mixed::mixed_workload;
+use nested::{nested_seq, flat_once, separate_pools_once, same_pool_watchdog};
+use openloop::{Arrival, fib_open_loop};
+use backend::{Backend, SpawnJoin, NaivePool};
+use manifest::{Manifest, Value};
+use pin::Pin;
+use span::Analysis;
+use treestats::TreeStats;
+use numa::Policy;
+use weak::{Scaling, WeakPoint, fib_calls, sort_units};
+use measure::{Samples, job_samples, seq_samples};
+use threadstats::PoolStats;
+use baseline::{Row, Verdict};
 
 
 fn main() {
     let mut samples: usize = 25;
-    let mut threads: Vec<usize> = vec![1,2,4];
+    let mut threads: Vec<String> = vec!["1".to_string(), "2".to_string(), "4".to_string()];
     let mut fib_args: Vec<usize> = vec![31];
     let mut sort_args: Vec<usize> = vec![0, 20000];
     let mut nqueens_args: Vec<usize> = vec![8];
     let mut sumtree_args: Vec<usize> = vec![12];
+    let mut sumtree_work: Vec<usize> = vec![0];
+    let mut sumtree_layouts: Vec<String> = vec!["nested".to_string()];
+    let mut sumtree_cutoffs: Vec<String> = vec![];
+    let mut uts_args: Vec<String> = vec!["T1".to_string()];
     let mut seq: bool = true;
+    let mut pool_modes: Vec<String> = vec!["reuse".to_string(), "fresh".to_string()];
+    let mut backends: Vec<String> = vec!["forkjoin".to_string()];
+    let mut weak_scaling: bool = false;
+    let mut pin: String = "none".to_string();
+    let mut numa_policies: Vec<String> = vec!["local".to_string()];
+    let mut trace_dir: String = String::new();
+    let mut span_analysis: bool = false;
+    let mut tree_stats: bool = false;
+    let mut save_baseline: String = String::new();
+    let mut compare: String = String::new();
+    let mut plot_dir: String = String::new();
+    let mut counters: bool = false;
+    let mut allocations: bool = false;
+    let mut allocator_report: Vec<String> = vec![];
+    let mut clients: usize = 1;
+    let mut inflight: usize = 4;
+    let mut jobs: usize = 1000;
+    let mut rates: Vec<f64> = vec![1000.0];
+    let mut arrival: String = "poisson".to_string();
 
     let mut functions: Vec<String> = vec![];
 
@@ -47,55 +122,236 @@ fn main() {
         ap.set_description("Measure performance of ForkJoin(https://github.com/faern/forkjoin)");
 
         ap.refer(&mut samples).add_option(&["-s", "--samples"], Store, "Number of samples to collect for each benchmark");
-        ap.refer(&mut threads).add_option(&["-t", "--threads"], List, "Number of threads to run on");
+        ap.refer(&mut threads).add_option(&["-t", "--threads"], List, "Number of threads to run on (N, a..=b[:step], pow2, physical, logical, max is the number of logical CPUs)");
         ap.refer(&mut fib_args).add_option(&["--fib"], List, "Arguments to fib");
         ap.refer(&mut sort_args).add_option(&["--sort"], List, "Size of lists to sort by quicksort");
         ap.refer(&mut nqueens_args).add_option(&["--nqueens"], List, "Size of chessboard");
         ap.refer(&mut sumtree_args).add_option(&["--sumtree"], List, "Depth of tree in sumtree");
+        ap.refer(&mut sumtree_work).add_option(&["--sumtree-work"], List, "Work per node in sumtree, in nanoseconds");
+        ap.refer(&mut sumtree_layouts).add_option(&["--sumtree-layout"], List, "Memory layout of sumtree trees (nested, bfs, dfs)");
+        ap.refer(&mut sumtree_cutoffs).add_option(&["--sumtree-cutoff"], List, "Sequential cutoffs for nested sumtree (size:<nodes>, depth:<depth>)");
+        ap.refer(&mut uts_args).add_option(&["--uts"], List, "UTS trees to search (T1, T3)");
         ap.refer(&mut seq).add_option(&["--noseq"], StoreFalse, "Disable running of sequential algorithms");
+        ap.refer(&mut pool_modes).add_option(&["--pool-mode"], List, "Reuse one pool for all iterations and/or create a fresh one in every iteration (reuse, fresh). With both the startup cost, fresh minus reuse, is printed");
+        ap.refer(&mut backends).add_option(&["--backend"], List, "Implementations to run fib, qsort, mergesort, nqueens_reduce and sumtree on (forkjoin, spawn, naivepool)");
+        ap.refer(&mut weak_scaling).add_option(&["--weak-scaling"], StoreTrue, "Grow the input of fib, qsort, mergesort and sumtree with the thread count and report time per unit of work per thread");
+        ap.refer(&mut pin).add_option(&["--pin"], Store, "Pin the driver to the first CPU, the ForkJoin workers one each to the rest, and keep the other backends on the rest (none, compact, scatter, list:<cpus>)");
+        ap.refer(&mut numa_policies).add_option(&["--numa"], List, "Placement of the ForkJoin qsort and mergesort input on NUMA nodes, also in the baseline modes (local, first-touch, interleave)");
+        ap.refer(&mut trace_dir).add_option(&["--trace"], Store, "Run one job of fib, qsort, mergesort and sumtree on the highest thread count and write its timeline in Chrome trace format to this directory");
+        ap.refer(&mut span_analysis).add_option(&["--span"], StoreTrue, "Compute work, span and parallelism of fib, qsort, mergesort, nqueens_reduce and sumtree and compare the bounds with measured speedups");
+        ap.refer(&mut tree_stats).add_option(&["--treestats"], StoreTrue, "Walk the task tree of fib, qsort, mergesort, nqueens and sumtree sequentially and print its size, depths, fan-outs and leaf times");
+        ap.refer(&mut save_baseline).add_option(&["--save-baseline"], Store, "Measure job times of fib, qsort, mergesort, nqueens_reduce and sumtree and save them under this name");
+        ap.refer(&mut compare).add_option(&["--compare"], Store, "Measure like --save-baseline and compare with the baseline of this name, exits with 1 on a regression");
+        ap.refer(&mut plot_dir).add_option(&["--plot"], Store, "Measure like --save-baseline and write SVG plots and an index.html to this directory");
+        ap.refer(&mut counters).add_option(&["--counters"], StoreTrue, "Measure like --save-baseline and print hardware counters per job, only the baseline modes count them (needs the perf-counters feature)");
+        ap.refer(&mut allocations).add_option(&["--allocations"], StoreTrue, "Measure like --save-baseline and print allocations per job (needs the count-allocations feature)");
+        ap.refer(&mut allocator_report).add_option(&["--allocator-report"], List, "Compare the saved baselines of these names, one per allocator build (system-alloc, slab-alloc features), without measuring");
+        ap.refer(&mut clients).add_option(&["--clients"], Store, "Number of client threads submitting jobs in throughput mode, or receiving results in open loop mode");
+        ap.refer(&mut inflight).add_option(&["--inflight"], Store, "Number of outstanding jobs per client in throughput mode");
+        ap.refer(&mut jobs).add_option(&["--jobs"], Store, "Total number of jobs to submit in throughput mode");
+        ap.refer(&mut rates).add_option(&["--rate"], List, "Job arrival rates, in jobs/s, in open loop mode");
+        ap.refer(&mut arrival).add_option(&["--arrival"], Store, "Job arrival process in open loop mode (poisson, constant)");
         ap.refer(&mut functions).add_argument("functions", List, "List of functions to benchmark").required();
 
         ap.parse_args_or_exit();
     }
     println!("==================================");
+    let threads = cpus::parse_threads(&threads);
+    let pin_cpus = Pin::parse(&pin).cpus();
+    let (driver_cpu, worker_cpus) = pin::split(&pin_cpus);
+    let mut manifest = Manifest::collect();
+    manifest.fields.push(("pin", Value::Str(Some(pin.clone()))));
+    manifest.fields.push(("pin_driver_cpu", Value::List(driver_cpu.iter().map(|c| c.to_string()).collect())));
+    manifest.fields.push(("pin_worker_cpus", Value::List(worker_cpus.iter().map(|c| c.to_string()).collect())));
+    if let Err(e) = manifest.save(Path::new(".criterion")) {
+        println!("Warning: could not save run manifest: {}", e);
+    }
     println!("Number of samples: {}", samples);
+    println!("CPUs: {} logical, {} physical", cpus::logical(), cpus::physical());
     println!("Threads: {:?}", threads);
     println!("Fib arguments: {:?}", fib_args);
     println!("Sorting vector sizes: {:?}", sort_args);
     println!("Nqueens arguments: {:?}", nqueens_args);
     println!("Sumtree depths: {:?}", sumtree_args);
+    println!("Sumtree work per node (ns): {:?}", sumtree_work);
+    println!("Sumtree layouts: {:?}", sumtree_layouts);
+    println!("Sumtree cutoffs: {:?}", sumtree_cutoffs);
+    println!("UTS trees: {:?}", uts_args);
+    println!("Pool modes: {:?}", pool_modes);
+    println!("Backends: {:?}", backends);
+    println!("Weak scaling: {}", weak_scaling);
+    println!("NUMA nodes: {:?}, sort input placement: {:?}", numa::nodes(), numa_policies);
+    println!("Trace to: {:?}", trace_dir);
+    println!("Work/span analysis: {}, task tree statistics: {}", span_analysis, tree_stats);
+    println!("Pinning: {}, driver on CPU {:?}, workers on CPUs {:?}", pin, driver_cpu, worker_cpus);
+    println!("Save baseline: {:?}, compare with: {:?}, plot to: {:?}", save_baseline, compare, plot_dir);
+    println!("Allocator: {}", allocs::allocator());
+    println!("Worker statistics: {}", if cfg!(feature = "threadstats") { "printed after every ForkJoin benchmark and in the measuring modes (--save-baseline, --compare, --plot, --counters, --allocations)" } else { "build with --features threadstats" });
+    println!("Hardware counters: {}, allocations: {}", counters, allocations);
+    println!("Allocator report of: {:?}", allocator_report);
+    println!("Throughput clients/inflight/jobs: {}/{}/{}", clients, inflight, jobs);
+    println!("Open loop rates (jobs/s): {:?}, arrival: {}", rates, arrival);
     println!("Benchmarked functions: {:?}", functions);
+    println!("Run manifest: .criterion/manifest.json");
     println!("==================================");
 
+    let sumtree_work = calibrate_work(&sumtree_work);
+    let sumtree_layouts: Vec<Layout> = sumtree_layouts.iter().map(|l| Layout::parse(l)).collect();
+    let sumtree_cutoffs: Vec<Cutoff> = sumtree_cutoffs.iter().map(|c| Cutoff::parse(c)).collect();
+    let pool_modes: Vec<PoolMode> = pool_modes.iter().map(|m| PoolMode::parse(m)).collect();
+    let arrival = Arrival::parse(&arrival);
+    let backends: Vec<Backend> = backends.iter().map(|b| Backend::parse(b)).collect();
+    let mut numa_policies: Vec<Policy> = numa_policies.iter().map(|p| Policy::parse(p)).collect();
+    if numa::nodes().len() <= 1 && numa_policies.iter().any(|&p| p != Policy::Local) {
+        println!("Single NUMA node, running the sort benchmarks with local placement only");
+        numa_policies = vec![Policy::Local];
+    }
+
+    if !allocator_report.is_empty() {
+        let baselines: Vec<Vec<Row>> = allocator_report.iter().map(|name| {
+            baseline::load(name).unwrap_or_else(|e| panic!("Could not load baseline {}: {}", name, e))
+        }).collect();
+        let workloads: Vec<String> = functions.iter().flat_map(|f| baseline_workloads(f, &fib_args, &sort_args, &nqueens_args, &sumtree_args, &sumtree_work)).collect();
+        print_allocator_report(&allocator_report, &baselines, &workloads);
+        return;
+    }
+
+    let measuring = !save_baseline.is_empty() || !compare.is_empty() || !plot_dir.is_empty() || counters || allocations;
+    let startup_cpus = pin::current_affinity();
+    spawnpool::pin_workers(worker_cpus);
+    if let Some(cpu) = driver_cpu {
+        if !pin::pin_current(cpu) {
+            println!("Warning: could not pin the driver thread to CPU {}", cpu);
+        }
+    }
+
+    if !trace_dir.is_empty() {
+        fs::create_dir_all(&trace_dir).unwrap_or_else(|e| panic!("Could not create {}: {}", trace_dir, e));
+        let t = *threads.last().unwrap();
+        // The last argument, like mixed, since the first default sort argument is an empty input
+        let fib_arg = *fib_args.last().expect("tracing needs a fib argument");
+        let sort_arg = *sort_args.last().expect("tracing needs a sort argument");
+        let sumtree_arg = *sumtree_args.last().expect("tracing needs a sumtree argument");
+        for function in functions.iter() {
+            trace_job(function, Path::new(&trace_dir), worker_cpus, t, fib_arg, sort_arg, sumtree_arg, sumtree_work[0]);
+        }
+        return;
+    }
+
+    if tree_stats {
+        for function in functions.iter() {
+            tree_workloads(function, &fib_args, &sort_args, &nqueens_args, &sumtree_args, &sumtree_work);
+        }
+        return;
+    }
+
+    if span_analysis {
+        for function in functions.iter() {
+            span_workloads(function, samples, worker_cpus, &threads, &fib_args, &sort_args, &nqueens_args, &sumtree_args, &sumtree_work);
+        }
+        return;
+    }
+
+    if measuring {
+        let old = if compare.is_empty() { None } else {
+            Some(baseline::load(&compare).unwrap_or_else(|e| panic!("Could not load baseline {}: {}", compare, e)))
+        };
+        let rows: Vec<Row> = functions.iter().flat_map(|f| baseline_rows(f, samples, worker_cpus, seq, &threads, &numa_policies, &fib_args, &sort_args, &nqueens_args, &sumtree_args, &sumtree_work, &sumtree_cutoffs)).collect();
+        if counters {
+            print_counters(&rows);
+        }
+        if allocations {
+            print_allocations(&rows);
+        }
+        if cfg!(feature = "threadstats") {
+            print_threadstats(&rows);
+        }
+        let regressed = match old {
+            Some(ref old) => compare_baseline(&compare, old, &rows),
+            None => false,
+        };
+        if !plot_dir.is_empty() {
+            plot::write_plots(Path::new(&plot_dir), &rows).unwrap_or_else(|e| panic!("Could not write plots to {}: {}", plot_dir, e));
+            println!("Wrote plots to {}/index.html", plot_dir);
+        }
+        if !save_baseline.is_empty() {
+            baseline::save(&save_baseline, &rows, &manifest).unwrap_or_else(|e| panic!("Could not save baseline {}: {}", save_baseline, e));
+            println!("Saved baseline {}", save_baseline);
+        }
+        if regressed {
+            process::exit(1);
+        }
+        return;
+    }
+
     let mut criterion = Criterion::default();
     criterion.sample_size(samples);
 
     for function in functions {
+        if weak_scaling {
+            match function.as_ref() {
+                "fib" => weak_fib(samples, worker_cpus, &fib_args, &threads),
+                "qsort" => weak_sort(samples, worker_cpus, "qsort", quicksort_task, quicksort_join, &sort_args, &threads),
+                "mergesort" => weak_sort(samples, worker_cpus, "mergesort", mergesort_task, mergesort_join, &sort_args, &threads),
+                "sumtree_unbalanced" => weak_sumtree(samples, worker_cpus, "sumtree_unbalanced", gen_unbalanced_tree, Scaling::Log2, &sumtree_args, &sumtree_work, &threads),
+                "sumtree_list" => weak_sumtree(samples, worker_cpus, "sumtree_listtree", gen_list_tree, Scaling::Linear, &sumtree_args, &sumtree_work, &threads),
+                "sumtree_balanced" => weak_sumtree(samples, worker_cpus, "sumtree_balanced", gen_balanced_tree, Scaling::Log2, &sumtree_args, &sumtree_work, &threads),
+                other => println!("Weak scaling is not supported for: {}", other),
+            }
+            continue;
+        }
+        // Threads the other functions start themselves would inherit the driver's CPU
+        let unpinned = driver_cpu.is_some() && !places_workers(&function);
+        if unpinned {
+            println!("Warning: --pin does not apply to {}, running it unpinned", function);
+            pin::allow_current(&startup_cpus);
+        }
         match function.as_ref() {
             "spawn" => bench_spawn(&mut criterion, &threads),
             "spawn_drop" => bench_spawn_drop(&mut criterion, &threads),
             "spawn_schedule_drop" => bench_spawn_schedule_drop(&mut criterion, &threads),
-            "fib" => bench_fib(&mut criterion, &fib_args, &threads, seq),
-            "fib_no_threshold" => bench_fib_no_threshold(&mut criterion, &fib_args, &threads, seq),
+            "spawn_lifecycle" => spawn_lifecycle(samples, &threads),
+            "fib" => bench_fib(&mut criterion, &fib_args, &threads, &pool_modes, &backends, seq),
+            "fib_no_threshold" => bench_fib_no_threshold(&mut criterion, &fib_args, &threads, &pool_modes, seq),
             "seqfib_spam" => bench_seqfib_spam(&mut criterion, &fib_args, &threads),
-            "qsort" => bench_qsort(&mut criterion, &sort_args, &threads, seq),
-            "mergesort" => bench_mergesort(&mut criterion, &sort_args, &threads, seq),
-            "nqueens_reduce" => bench_nqueens_reduce(&mut criterion, &nqueens_args, &threads, seq),
-            "nqueens_search" => bench_nqueens_search(&mut criterion, &nqueens_args, &threads, seq),
-            "nqueens_search_first" => bench_nqueens_search_first(&mut criterion, &nqueens_args, &threads, seq),
-            "sumtree_unbalanced" => bench_sumtree_unbalanced(&mut criterion, &sumtree_args, &threads, seq),
-            "sumtree_list" => bench_sumtree_listtree(&mut criterion, &sumtree_args, &threads, seq),
-            "sumtree_balanced" => bench_sumtree_balanced(&mut criterion, &sumtree_args, &threads, seq),
+            "qsort" => bench_qsort(&mut criterion, &sort_args, &threads, &pool_modes, &numa_policies, &backends, seq),
+            "mergesort" => bench_mergesort(&mut criterion, &sort_args, &threads, &pool_modes, &numa_policies, &backends, seq),
+            "nqueens_reduce" => bench_nqueens_reduce(&mut criterion, &nqueens_args, &threads, &pool_modes, &backends, seq),
+            "nqueens_search" => bench_nqueens_search(&mut criterion, &nqueens_args, &threads, &pool_modes, seq),
+            "nqueens_search_first" => bench_nqueens_search_first(&mut criterion, &nqueens_args, &threads, &pool_modes, seq),
+            "sumtree_unbalanced" => bench_sumtree(&mut criterion, "sumtree_unbalanced", gen_unbalanced_tree, &sumtree_args, &sumtree_work, &sumtree_layouts, &sumtree_cutoffs, &threads, &pool_modes, &backends, seq),
+            "sumtree_list" => bench_sumtree(&mut criterion, "sumtree_listtree", gen_list_tree, &sumtree_args, &sumtree_work, &sumtree_layouts, &sumtree_cutoffs, &threads, &pool_modes, &backends, seq),
+            "sumtree_balanced" => bench_sumtree(&mut criterion, "sumtree_balanced", gen_balanced_tree, &sumtree_args, &sumtree_work, &sumtree_layouts, &sumtree_cutoffs, &threads, &pool_modes, &backends, seq),
+            "uts" => bench_uts(&mut criterion, &uts_args, &threads, &pool_modes, seq),
             "fib_once" => fib_once(&fib_args, &threads),
             "fib_no_threshold_once" => fib_no_threshold_once(&fib_args, &threads),
             "qsort_once" => qsort_once(&sort_args, &threads),
             "mergesort_once" => mergesort_once(&sort_args, &threads),
             "nqueens_reduce_once" => nqueens_reduce_once(&nqueens_args, &threads),
-            "sumtree_unbalanced_once" => sumtree_unbalanced_once(&sumtree_args, &threads),
-            "sumtree_list_once" => sumtree_listtree_once(&sumtree_args, &threads),
-            "sumtree_balanced_once" => sumtree_balanced_once(&sumtree_args, &threads),
+            "sumtree_unbalanced_once" => sumtree_once("sumtree_unbalanced", gen_unbalanced_tree, &sumtree_args, &sumtree_work, &sumtree_layouts, &sumtree_cutoffs, &threads),
+            "sumtree_list_once" => sumtree_once("sumtree_list", gen_list_tree, &sumtree_args, &sumtree_work, &sumtree_layouts, &sumtree_cutoffs, &threads),
+            "sumtree_balanced_once" => sumtree_once("sumtree_balanced", gen_balanced_tree, &sumtree_args, &sumtree_work, &sumtree_layouts, &sumtree_cutoffs, &threads),
+            "uts_once" => uts_once(&uts_args, &threads),
+            "throughput_fib" => throughput_fib(&fib_args, &threads, clients, inflight, jobs),
+            "throughput_qsort" => throughput_qsort(&sort_args, &threads, clients, inflight, jobs),
+            "mixed" => mixed(samples, &fib_args, &sumtree_args, &sort_args, &threads),
+            "nested" => nested(samples, &fib_args, &sumtree_args, &threads),
+            "openloop_fib" => openloop_fib(&fib_args, &threads, clients, arrival, &rates, jobs),
             other => panic!("Invalid function to benchmark: {}", other),
         }
+        if let (true, Some(cpu)) = (unpinned, driver_cpu) {
+            pin::pin_current(cpu);
+        }
+    }
+}
+
+/// Whether the criterion benchmarks of `function` put every thread they start on the
+/// worker CPUs, so that the driver can stay pinned.
+fn places_workers(function: &str) -> bool {
+    match function {
+        "fib" | "fib_no_threshold" | "qsort" | "mergesort" | "nqueens_reduce" | "nqueens_search" | "nqueens_search_first" |
+        "sumtree_unbalanced" | "sumtree_list" | "sumtree_balanced" | "uts" => true,
+        _ => false,
     }
 }
 
@@ -126,27 +382,38 @@ fn bench_spawn_schedule_drop(criterion: &mut Criterion, threads: &[usize]) {
     criterion.bench_compare_implementations("spawn_schedule_drop", funs, &0);
 }
 
-fn bench_fib(criterion: &mut Criterion, args: &[usize], threads: &[usize], seq: bool) {
+fn bench_fib(criterion: &mut Criterion, args: &[usize], threads: &[usize], modes: &[PoolMode], backends: &[Backend], seq: bool) {
     for arg in args {
         let mut funs: Vec<Fun<usize>> = Vec::new();
         if seq {funs.push(Fun::new("seq", |b,i| seqfib(b, i)));}
-        for &t in threads.iter() {
-            funs.push(Fun::new(&format!("T{}", t), move |b,i| parfib(b, t, i)));
+        if backends.contains(&Backend::ForkJoin) {
+            for &mode in modes {
+                for &t in threads.iter() {
+                    funs.push(pool_fun(format!("T{}{}", t, mode.suffix()), move |b,i| parfib(b, t, mode, i)));
+                }
+            }
         }
+        funs.extend(backend_funs(backends, threads, |b, j, i| backend_fib(b, j, i), |b, j, i| backend_fib(b, j, i)));
 
-        criterion.bench_compare_implementations(&format!("fib_{}", arg), funs, arg);
+        let name = format!("fib_{}", arg);
+        criterion.bench_compare_implementations(&name, funs, arg);
+        print_pool_report(&name);
     }
 }
 
-fn bench_fib_no_threshold(criterion: &mut Criterion, args: &[usize], threads: &[usize], seq: bool) {
+fn bench_fib_no_threshold(criterion: &mut Criterion, args: &[usize], threads: &[usize], modes: &[PoolMode], seq: bool) {
     for arg in args {
         let mut funs: Vec<Fun<usize>> = Vec::new();
         if seq {funs.push(Fun::new("seq", |b,i| seqfib(b, i)));}
-        for &t in threads.iter() {
-            funs.push(Fun::new(&format!("T{}", t), move |b,i| parfib_no_threshold(b, t, i)));
+        for &mode in modes {
+            for &t in threads.iter() {
+                funs.push(pool_fun(format!("T{}{}", t, mode.suffix()), move |b,i| parfib_no_threshold(b, t, mode, i)));
+            }
         }
 
-        criterion.bench_compare_implementations(&format!("fib_no_threshold_{}", arg), funs, arg);
+        let name = format!("fib_no_threshold_{}", arg);
+        criterion.bench_compare_implementations(&name, funs, arg);
+        print_pool_report(&name);
     }
 }
 
@@ -161,114 +428,295 @@ fn bench_seqfib_spam(criterion: &mut Criterion, args: &[usize], threads: &[usize
     }
 }
 
-fn bench_qsort(criterion: &mut Criterion, args: &[usize], threads: &[usize], seq: bool) {
+fn bench_qsort(criterion: &mut Criterion, args: &[usize], threads: &[usize], modes: &[PoolMode], numa_policies: &[Policy], backends: &[Backend], seq: bool) {
     let seed = 893475343;
     for arg in args {
         let mut funs: Vec<Fun<usize>> = Vec::new();
         if seq {funs.push(Fun::new("seq", move |b,i| seq_qsort(b, *i, move |d| create_vec_rnd(seed, d))));}
-        for &t in threads.iter() {
-            funs.push(Fun::new(&format!("T{}", t), move |b,i| par_qsort(b, t, *i, move |d| create_vec_rnd(seed, d))));
+        if backends.contains(&Backend::ForkJoin) {
+            for &mode in modes {
+                for &t in threads.iter() {
+                    for &numa in numa_policies {
+                        funs.push(pool_fun(format!("T{}{}{}", t, mode.suffix(), numa.suffix()), move |b,i| par_qsort(b, t, mode, numa, *i, move |d| create_vec_rnd(seed, d))));
+                    }
+                }
+            }
         }
+        funs.extend(backend_funs::<usize, _, _>(backends, threads, move |b, j, i| backend_qsort(b, j, *i, move |d| create_vec_rnd(seed, d)), move |b, j, i| backend_qsort(b, j, *i, move |d| create_vec_rnd(seed, d))));
 
-        criterion.bench_compare_implementations(&format!("qsort_{}", arg), funs, arg);
+        let name = format!("qsort_{}", arg);
+        criterion.bench_compare_implementations(&name, funs, arg);
+        print_pool_report(&name);
     }
 }
 
-fn bench_mergesort(criterion: &mut Criterion, args: &[usize], threads: &[usize], seq: bool) {
+fn bench_mergesort(criterion: &mut Criterion, args: &[usize], threads: &[usize], modes: &[PoolMode], numa_policies: &[Policy], backends: &[Backend], seq: bool) {
     let seed = 893475343;
     for arg in args {
         let mut funs: Vec<Fun<usize>> = Vec::new();
         if seq {funs.push(Fun::new("seq", move |b,i| seq_mergesort(b, *i, move |d| create_vec_rnd(seed, d))));}
-        for &t in threads.iter() {
-            funs.push(Fun::new(&format!("T{}", t), move |b,i| par_mergesort(b, t, *i, move |d| create_vec_rnd(seed, d))));
+        if backends.contains(&Backend::ForkJoin) {
+            for &mode in modes {
+                for &t in threads.iter() {
+                    for &numa in numa_policies {
+                        funs.push(pool_fun(format!("T{}{}{}", t, mode.suffix(), numa.suffix()), move |b,i| par_mergesort(b, t, mode, numa, *i, move |d| create_vec_rnd(seed, d))));
+                    }
+                }
+            }
         }
+        funs.extend(backend_funs::<usize, _, _>(backends, threads, move |b, j, i| backend_mergesort(b, j, *i, move |d| create_vec_rnd(seed, d)), move |b, j, i| backend_mergesort(b, j, *i, move |d| create_vec_rnd(seed, d))));
 
-        criterion.bench_compare_implementations(&format!("mergesort_{}", arg), funs, arg);
+        let name = format!("mergesort_{}", arg);
+        criterion.bench_compare_implementations(&name, funs, arg);
+        print_pool_report(&name);
     }
 }
 
-fn bench_nqueens_reduce(criterion: &mut Criterion, args: &[usize], threads: &[usize], seq: bool) {
+fn bench_nqueens_reduce(criterion: &mut Criterion, args: &[usize], threads: &[usize], modes: &[PoolMode], backends: &[Backend], seq: bool) {
     for arg in args {
         let mut funs: Vec<Fun<usize>> = Vec::new();
         if seq {funs.push(Fun::new("seq", move |b,i| seq_nqueens_reduce(b, i)));}
-        for &t in threads.iter() {
-            funs.push(Fun::new(&format!("T{}", t), move |b,i| par_nqueens_reduce(b, t, i)));
+        if backends.contains(&Backend::ForkJoin) {
+            for &mode in modes {
+                for &t in threads.iter() {
+                    funs.push(pool_fun(format!("T{}{}", t, mode.suffix()), move |b,i| par_nqueens_reduce(b, t, mode, i)));
+                }
+            }
         }
+        funs.extend(backend_funs(backends, threads, |b, j, i| backend_nqueens_reduce(b, j, i), |b, j, i| backend_nqueens_reduce(b, j, i)));
 
-        criterion.bench_compare_implementations(&format!("nqueens_reduce_{}", arg), funs, arg);
+        let name = format!("nqueens_reduce_{}", arg);
+        criterion.bench_compare_implementations(&name, funs, arg);
+        print_pool_report(&name);
     }
 }
 
-fn bench_nqueens_search(criterion: &mut Criterion, args: &[usize], threads: &[usize], seq: bool) {
+fn bench_nqueens_search(criterion: &mut Criterion, args: &[usize], threads: &[usize], modes: &[PoolMode], seq: bool) {
     for arg in args {
         let mut funs: Vec<Fun<usize>> = Vec::new();
         if seq {funs.push(Fun::new("seq", move |b,i| seq_nqueens_reduce(b, i)));} // Sequential only exists in reduce style for now
-        for &t in threads.iter() {
-            funs.push(Fun::new(&format!("T{}", t), move |b,i| par_nqueens_search(b, t, i)));
+        for &mode in modes {
+            for &t in threads.iter() {
+                funs.push(pool_fun(format!("T{}{}", t, mode.suffix()), move |b,i| par_nqueens_search(b, t, mode, i)));
+            }
         }
 
-        criterion.bench_compare_implementations(&format!("nqueens_search_{}", arg), funs, arg);
+        let name = format!("nqueens_search_{}", arg);
+        criterion.bench_compare_implementations(&name, funs, arg);
+        print_pool_report(&name);
     }
 }
 
-fn bench_nqueens_search_first(criterion: &mut Criterion, args: &[usize], threads: &[usize], seq: bool) {
+fn bench_nqueens_search_first(criterion: &mut Criterion, args: &[usize], threads: &[usize], modes: &[PoolMode], seq: bool) {
     for arg in args {
         let mut funs: Vec<Fun<usize>> = Vec::new();
         if seq {funs.push(Fun::new("seq", move |b,i| seq_nqueens_search(b, i)));} // Sequential only exists in reduce style for now
-        for &t in threads.iter() {
-            funs.push(Fun::new(&format!("T{}", t), move |b,i| par_nqueens_search_first(b, t, i)));
+        for &mode in modes {
+            for &t in threads.iter() {
+                funs.push(pool_fun(format!("T{}{}", t, mode.suffix()), move |b,i| par_nqueens_search_first(b, t, mode, i)));
+            }
         }
 
-        criterion.bench_compare_implementations(&format!("nqueens_search_first_{}", arg), funs, arg);
+        let name = format!("nqueens_search_first_{}", arg);
+        criterion.bench_compare_implementations(&name, funs, arg);
+        print_pool_report(&name);
     }
 }
 
-fn bench_sumtree_unbalanced(criterion: &mut Criterion, args: &[usize], threads: &[usize], seq: bool) {
+fn bench_sumtree(criterion: &mut Criterion, shape: &str, gen: fn(usize) -> Tree, args: &[usize], works: &[(usize, usize)], layouts: &[Layout], cutoffs: &[Cutoff], threads: &[usize], modes: &[PoolMode], backends: &[Backend], seq: bool) {
     for arg in args {
-        let tree = gen_unbalanced_tree(*arg);
-        let tree2 = tree.clone();
+        for &(work, spins) in works {
+            let mut tree = gen(*arg);
+            set_work(&mut tree, spins);
 
-        let mut funs: Vec<Fun<usize>> = Vec::new();
-        if seq {funs.push(Fun::new("seq", move |b,_| seq_sumtree(b, &tree2)));}
-        for &t in threads.iter() {
-            let tree_clone = tree.clone();
-            funs.push(Fun::new(&format!("T{}", t), move |b,_| par_sumtree(b, t, &tree_clone)));
-        }
+            for &layout in layouts {
+                let mut funs: Vec<Fun<usize>> = Vec::new();
+                if layout == Layout::Nested {
+                    let tree2 = tree.clone();
+                    if seq {funs.push(Fun::new("seq", move |b,_| seq_sumtree(b, &tree2)));}
+                    if backends.contains(&Backend::ForkJoin) {
+                        for &mode in modes {
+                            for &t in threads.iter() {
+                                let tree_clone = tree.clone();
+                                funs.push(pool_fun(format!("T{}{}", t, mode.suffix()), move |b,_| par_sumtree(b, t, mode, &tree_clone)));
+                            }
+                        }
+                    }
+                    let (tree_spawn, tree_pool) = (tree.clone(), tree.clone());
+                    funs.extend(backend_funs(backends, threads, move |b, j, _| backend_sumtree(b, j, &tree_spawn), move |b, j, _| backend_sumtree(b, j, &tree_pool)));
+                    if backends.contains(&Backend::ForkJoin) {
+                        for &cutoff in cutoffs {
+                            for &mode in modes {
+                                for &t in threads.iter() {
+                                    let tree_clone = tree.clone();
+                                    funs.push(pool_fun(format!("T{}_{}{}", t, cutoff.name(), mode.suffix()), move |b,_| par_sumtree_cutoff(b, t, mode, &tree_clone, cutoff)));
+                                }
+                            }
+                        }
+                    }
+                } else {
+                    let flat = FlatTree::from_tree(&tree, layout);
+                    let flat2 = flat.clone();
+                    if seq {funs.push(Fun::new("seq", move |b,_| seq_flat_sumtree(b, &flat2)));}
+                    if backends.contains(&Backend::ForkJoin) {
+                        for &mode in modes {
+                            for &t in threads.iter() {
+                                let flat_clone = flat.clone();
+                                funs.push(pool_fun(format!("T{}{}", t, mode.suffix()), move |b,_| par_flat_sumtree(b, t, mode, &flat_clone)));
+                            }
+                        }
+                    }
+                }
 
-        criterion.bench_compare_implementations(&format!("sumtree_unbalanced_{}", arg), funs, arg);
+                let name = sumtree_name(shape, layout, *arg, work);
+                criterion.bench_compare_implementations(&name, funs, arg);
+                print_pool_report(&name);
+            }
+        }
     }
 }
 
-fn bench_sumtree_listtree(criterion: &mut Criterion, args: &[usize], threads: &[usize], seq: bool) {
+fn bench_uts(criterion: &mut Criterion, args: &[String], threads: &[usize], modes: &[PoolMode], seq: bool) {
     for arg in args {
-        let tree = gen_list_tree(*arg);
-        let tree2 = tree.clone();
+        let params = uts_params(arg);
 
         let mut funs: Vec<Fun<usize>> = Vec::new();
-        if seq {funs.push(Fun::new("seq", move |b,_| seq_sumtree(b, &tree2)));}
-        for &t in threads.iter() {
-            let tree_clone = tree.clone();
-            funs.push(Fun::new(&format!("T{}", t), move |b,_| par_sumtree(b, t, &tree_clone)));
+        if seq {funs.push(Fun::new("seq", move |b,_| seq_uts(b, params)));}
+        for &mode in modes {
+            for &t in threads.iter() {
+                funs.push(pool_fun(format!("T{}{}", t, mode.suffix()), move |b,_| par_uts(b, t, mode, params)));
+            }
         }
 
-        criterion.bench_compare_implementations(&format!("sumtree_listtree_{}", arg), funs, arg);
+        let name = format!("uts_{}", arg);
+        criterion.bench_compare_implementations(&name, funs, &0);
+        print_pool_report(&name);
     }
 }
 
-fn bench_sumtree_balanced(criterion: &mut Criterion, args: &[usize], threads: &[usize], seq: bool) {
-    for arg in args {
-        let tree = gen_balanced_tree(*arg);
-        let tree2 = tree.clone();
+thread_local!(static POOL_RUNS: RefCell<Vec<(String, PoolRuns)>> = RefCell::new(Vec::new()));
 
-        let mut funs: Vec<Fun<usize>> = Vec::new();
-        if seq {funs.push(Fun::new("seq", move |b,_| seq_sumtree(b, &tree2)));}
+/// A ForkJoin implementation benchmarked through `spawnpool::bench_pool`. Its iteration
+/// times and worker statistics are kept under `name` until the next `print_pool_report`.
+fn pool_fun<I, F>(name: String, f: F) -> Fun<I> where
+    F: Fn(&mut Bencher, &I) + 'static
+{
+    Fun::new(&name.clone(), move |b, i| {
+        f(b, i);
+        let runs = spawnpool::take_pool_runs();
+        POOL_RUNS.with(|pool_runs| {
+            let mut pool_runs = pool_runs.borrow_mut();
+            if let Some(&mut (_, ref mut all)) = pool_runs.iter_mut().find(|entry| entry.0 == name) {
+                all.times.extend(runs.times);
+                if let Some(stats) = runs.threadstats {
+                    threadstats::accumulate(&mut all.threadstats, stats);
+                }
+                return;
+            }
+            pool_runs.push((name.clone(), runs));
+        });
+    })
+}
+
+/// Prints what the ForkJoin implementations of `workload` recorded since the last call.
+fn print_pool_report(workload: &str) {
+    let pool_runs = POOL_RUNS.with(|pool_runs| mem::replace(&mut *pool_runs.borrow_mut(), Vec::new()));
+    print_startup_cost(workload, &pool_runs);
+    print_pool_threadstats(workload, &pool_runs);
+}
+
+/// Prints how much longer the median iteration of every fresh pool implementation of
+/// `workload` took than the same implementation reusing its pool. Nothing unless both
+/// pool modes were run.
+fn print_startup_cost(workload: &str, pool_runs: &[(String, PoolRuns)]) {
+    let medians: Vec<(&str, u64)> = pool_runs.iter()
+        .filter(|&&(_, ref runs)| !runs.times.is_empty())
+        .map(|&(ref name, ref runs)| (&name[..], stats::median(&runs.times[..])))
+        .collect();
+    let (fresh_suffix, reuse_suffix) = (PoolMode::Fresh.suffix(), PoolMode::Reuse.suffix());
+    let mut printed = false;
+    for &(name, fresh) in medians.iter().filter(|&&(name, _)| name.contains(fresh_suffix)) {
+        let reuse_name = name.replace(fresh_suffix, reuse_suffix);
+        if let Some(&(_, reuse)) = medians.iter().find(|&&(n, _)| n == reuse_name) {
+            if !printed {
+                println!("Pool startup cost of {}, fresh minus reuse median iteration", workload);
+                printed = true;
+            }
+            let cost = if fresh >= reuse { format(fresh - reuse) } else { format!("-{}", format(reuse - fresh)) };
+            println!("{:>24}{:>14}", name.replace(fresh_suffix, ""), cost);
+        }
+    }
+    if printed {
+        println!("");
+    }
+}
+
+/// Worker statistics per iteration of every ForkJoin implementation of `workload`.
+/// Nothing without the `threadstats` feature.
+fn print_pool_threadstats(workload: &str, pool_runs: &[(String, PoolRuns)]) {
+    if pool_runs.iter().all(|&(_, ref runs)| runs.threadstats.is_none()) {
+        return;
+    }
+    println!("Worker statistics of {} per iteration", workload);
+    print_threadstats_header("impl");
+    for &(ref name, ref runs) in pool_runs {
+        if let Some(ref s) = runs.threadstats {
+            print_threadstats_row(name, stats::median(&runs.times[..]), s);
+        }
+    }
+    println!("");
+}
+
+/// One implementation per thread count for every backend except ForkJoin, which the
+/// callers add themselves for every pool mode. The same workload has to be passed once
+/// per backend type since closures can't be generic.
+fn backend_funs<I, S, P>(backends: &[Backend], threads: &[usize], spawn: S, pool: P) -> Vec<Fun<I>> where
+    I: 'static,
+    S: Fn(&mut Bencher, &SpawnJoin, &I) + 'static,
+    P: Fn(&mut Bencher, &NaivePool, &I) + 'static
+{
+    let (spawn, pool) = (Arc::new(spawn), Arc::new(pool));
+    let cpus = Arc::new(spawnpool::worker_cpus());
+    let mut funs: Vec<Fun<I>> = Vec::new();
+    for &backend in backends {
         for &t in threads.iter() {
-            let tree_clone = tree.clone();
-            funs.push(Fun::new(&format!("T{}", t), move |b,_| par_sumtree(b, t, &tree_clone)));
+            match backend {
+                Backend::ForkJoin => (),
+                Backend::Spawn => {
+                    let (spawn, cpus) = (spawn.clone(), cpus.clone());
+                    funs.push(Fun::new(&format!("spawn_T{}", t), move |b,i| (*spawn)(b, &SpawnJoin::for_threads(t, &cpus), i)));
+                },
+                Backend::NaivePool => {
+                    let (pool, cpus) = (pool.clone(), cpus.clone());
+                    funs.push(Fun::new(&format!("naivepool_T{}", t), move |b,i| (*pool)(b, &NaivePool::new(t, &cpus), i)));
+                },
+            }
         }
+    }
+    funs
+}
+
+/// Converts per node work in nanoseconds to `spin` iterations on this machine.
+fn calibrate_work(works_ns: &[usize]) -> Vec<(usize, usize)> {
+    let spins_per_ns = if works_ns.iter().any(|&w| w > 0) {
+        let spins_per_ns = spin::calibrate();
+        println!("Spin calibration: {:.3} iterations/ns", spins_per_ns);
+        spins_per_ns
+    } else {
+        0.0
+    };
+    works_ns.iter().map(|&w| (w, (w as f64 * spins_per_ns) as usize)).collect()
+}
 
-        criterion.bench_compare_implementations(&format!("sumtree_balanced_{}", arg), funs, arg);
+fn sumtree_name(shape: &str, layout: Layout, depth: usize, work: usize) -> String {
+    let mut name = match layout {
+        Layout::Nested => format!("{}_{}", shape, depth),
+        _ => format!("{}_{}_{}", shape, layout.name(), depth),
+    };
+    if work > 0 {
+        name.push_str(&format!("_work{}ns", work));
     }
+    name
 }
 
 fn time_once<F: FnMut()>(mut f: F) {
@@ -290,6 +738,29 @@ fn format(ns: u64) -> String {
     format!("{:.2} {}", t, prefix[prefix_i])
 }
 
+fn spawn_lifecycle(samples: usize, threads: &[usize]) {
+    println!("Pool lifecycle phases, median of {} samples", samples);
+    println!("All ready and first recv only count the samples where every worker picked up a task within a second");
+    println!("{:>8}{:>14}{:>14}{:>14}{:>14}{:>14}{:>14}{:>10}", "threads", "spawn", "init_algo", "first task", "all ready", "first recv", "drop", "timeouts");
+    for &t in threads {
+        let runs: Vec<_> = (0..samples).map(|_| lifecycle_once(t)).collect();
+        let phase = |f: &Fn(&spawnpool::Lifecycle) -> Option<u64>| {
+            let durations: Vec<u64> = runs.iter().filter_map(|l| f(l)).collect();
+            if durations.is_empty() { "-".to_string() } else { format(stats::median(&durations[..])) }
+        };
+        let timeouts = runs.iter().filter(|l| l.all_ready.is_none()).count();
+        println!("{:>8}{:>14}{:>14}{:>14}{:>14}{:>14}{:>14}{:>10}", format!("T{}", t),
+            phase(&|l| Some(l.spawned)),
+            phase(&|l| Some(l.initialized - l.spawned)),
+            phase(&|l| Some(l.first_task.saturating_sub(l.initialized))),
+            phase(&|l| l.all_ready.map(|ready| ready.saturating_sub(l.first_task))),
+            phase(&|l| l.all_ready.map(|ready| l.first_recv.saturating_sub(ready))),
+            phase(&|l| Some(l.dropped - l.first_recv)),
+            timeouts);
+    }
+    println!("");
+}
+
 fn fib_once(args: &[usize], threads: &[usize]) {
     for &arg in args {
         for &t in threads {
@@ -353,38 +824,669 @@ fn nqueens_reduce_once(args: &[usize], threads: &[usize]) {
     println!("");
 }
 
-fn sumtree_unbalanced_once(args: &[usize], threads: &[usize]) {
+fn sumtree_once(shape: &str, gen: fn(usize) -> Tree, args: &[usize], works: &[(usize, usize)], layouts: &[Layout], cutoffs: &[Cutoff], threads: &[usize]) {
     for &arg in args {
-        let tree = gen_unbalanced_tree(arg);
+        for &(work, spins) in works {
+            let mut tree = gen(arg);
+            set_work(&mut tree, spins);
+
+            for &layout in layouts {
+                let flat = if layout == Layout::Nested { None } else { Some(FlatTree::from_tree(&tree, layout)) };
+                for &t in threads {
+                    println!("Running {}({}, work {}ns, {})/T{}", shape, arg, work, layout.name(), t);
+                    match flat {
+                        None => time_once(|| drop(par_sumtree_once(t, &tree))),
+                        Some(ref flat) => time_once(|| drop(par_flat_sumtree_once(t, flat))),
+                    }
+                }
+                if flat.is_none() {
+                    for &cutoff in cutoffs {
+                        for &t in threads {
+                            println!("Running {}({}, work {}ns, {}, {})/T{}", shape, arg, work, layout.name(), cutoff.name(), t);
+                            time_once(|| drop(par_sumtree_cutoff_once(t, &tree, cutoff)));
+                        }
+                    }
+                }
+                println!("");
+            }
+        }
+    }
+    println!("");
+}
+
+fn uts_once(args: &[String], threads: &[usize]) {
+    for arg in args {
+        let params = uts_params(arg);
         for &t in threads {
-            println!("Running sumtree_balanced({})/T{}", arg, t);
-            time_once(|| drop(par_sumtree_once(t, &tree)));
+            println!("Running uts({})/T{}", arg, t);
+            time_once(|| drop(par_uts_once(t, params)));
         }
         println!("");
     }
     println!("");
 }
 
-fn sumtree_listtree_once(args: &[usize], threads: &[usize]) {
+fn throughput_fib(args: &[usize], threads: &[usize], clients: usize, inflight: usize, jobs: usize) {
     for &arg in args {
-        let tree = gen_list_tree(arg);
+        println!("Throughput fib({}), {} clients with {} jobs in flight each", arg, clients, inflight);
+        print_throughput_header();
         for &t in threads {
-            println!("Running sumtree_list({})/T{}", arg, t);
-            time_once(|| drop(par_sumtree_once(t, &tree)));
+            print_throughput(t, &fib_throughput(t, clients, inflight, jobs, arg));
         }
         println!("");
     }
-    println!("");
 }
 
-fn sumtree_balanced_once(args: &[usize], threads: &[usize]) {
+fn throughput_qsort(args: &[usize], threads: &[usize], clients: usize, inflight: usize, jobs: usize) {
     for &arg in args {
-        let tree = gen_balanced_tree(arg);
+        println!("Throughput qsort({}), {} clients with {} jobs in flight each", arg, clients, inflight);
+        print_throughput_header();
         for &t in threads {
-            println!("Running sumtree_balanced({})/T{}", arg, t);
-            time_once(|| drop(par_sumtree_once(t, &tree)));
+            print_throughput(t, &qsort_throughput(t, clients, inflight, jobs, arg));
+        }
+        println!("");
+    }
+}
+
+fn print_throughput_header() {
+    println!("{:>8}{:>14}{:>12}{:>12}{:>12}{:>12}", "threads", "jobs/s", "p50", "p90", "p99", "max");
+}
+
+fn print_throughput(threads: usize, throughput: &Throughput) {
+    let mut latencies = throughput.latencies.clone();
+    latencies.sort();
+    let jobs_per_sec = throughput.jobs as f64 / (throughput.elapsed_ns as f64 / 1e9);
+    if latencies.is_empty() {
+        println!("{:>8}{:>14.1}", format!("T{}", threads), jobs_per_sec);
+    } else {
+        println!("{:>8}{:>14.1}{:>12}{:>12}{:>12}{:>12}", format!("T{}", threads), jobs_per_sec,
+            format(stats::percentile(&latencies[..], 50.0)),
+            format(stats::percentile(&latencies[..], 90.0)),
+            format(stats::percentile(&latencies[..], 99.0)),
+            format(latencies[latencies.len() - 1]));
+    }
+}
+
+/// Runs fib, sumtree_unbalanced and qsort on one pool, with the last argument given for each.
+fn mixed(samples: usize, fib_args: &[usize], sumtree_args: &[usize], sort_args: &[usize], threads: &[usize]) {
+    let fib_arg = *fib_args.last().expect("mixed needs a fib argument");
+    let sumtree_arg = *sumtree_args.last().expect("mixed needs a sumtree argument");
+    let sort_arg = *sort_args.last().expect("mixed needs a sort argument");
+    let tree = gen_unbalanced_tree(sumtree_arg);
+
+    println!("Mixed fib({}), sumtree_unbalanced({}) and qsort({}) on one pool, median of {} rounds", fib_arg, sumtree_arg, sort_arg, samples);
+    println!("{:>8}{:>10}{:>12}{:>12}{:>10}", "threads", "algo", "alone", "mixed", "slowdown");
+    for &t in threads {
+        let results = mixed_workload(t, samples, fib_arg, &tree, sort_arg);
+        for r in results.iter() {
+            println!("{:>8}{:>10}{:>12}{:>12}{:>10.2}", format!("T{}", t), r.name, format(r.solo), format(r.mixed), r.slowdown());
+        }
+        let progress: Vec<f64> = results.iter().map(|r| 1.0 / r.slowdown()).collect();
+        println!("{:>8}{:>10} {:.3}", format!("T{}", t), "fairness", stats::jain_fairness(&progress[..]));
+    }
+    println!("");
+}
+
+/// Balanced trees of the sumtree depths, where every leaf computes fib. Compares forking
+/// fib in the same job with blocking on a job on a second pool or on the same pool.
+fn nested(samples: usize, fib_args: &[usize], sumtree_args: &[usize], threads: &[usize]) {
+    for &depth in sumtree_args {
+        let tree = gen_balanced_tree(depth);
+        for &fib_arg in fib_args {
+            let expected = nested_seq(&tree, fib_arg);
+
+            println!("Nested fib({}) in the leaves of sumtree_balanced({}), median of {} samples", fib_arg, depth, samples);
+            println!("{:>8}{:>12}{:>16}{:>10}{:>12}{:>10}", "threads", "flat", "separate pools", "slowdown", "same pool", "slowdown");
+            for &t in threads {
+                let flat: Vec<u64> = (0..samples).map(|_| {
+                    let (result, elapsed) = flat_once(t, &tree, fib_arg);
+                    assert_eq!(expected, result);
+                    elapsed
+                }).collect();
+                let flat = stats::median(&flat[..]);
+
+                let separate: Vec<u64> = (0..samples).map(|_| {
+                    let (result, elapsed) = separate_pools_once(t, t, &tree, fib_arg);
+                    assert_eq!(expected, result);
+                    elapsed
+                }).collect();
+                let separate = stats::median(&separate[..]);
+
+                let timeout = cmp::max(1_000_000_000, 20 * flat);
+                let same = match same_pool_watchdog(t, gen_balanced_tree, depth, fib_arg, samples, timeout) {
+                    Some(results) => {
+                        let times: Vec<u64> = results.iter().map(|&(result, elapsed)| {
+                            assert_eq!(expected, result);
+                            elapsed
+                        }).collect();
+                        let same = stats::median(&times[..]);
+                        format!("{:>12}{:>10.2}", format(same), same as f64 / flat as f64)
+                    },
+                    None => format!("{:>12}{:>10}", "deadlock", "-"),
+                };
+
+                println!("{:>8}{:>12}{:>16}{:>10.2}{}", format!("T{}", t), format(flat), format(separate), separate as f64 / flat as f64, same);
+            }
+            println!("");
+        }
+    }
+}
+
+fn weak_fib(samples: usize, pin: &[usize], args: &[usize], threads: &[usize]) {
+    for &arg in args {
+        let points: Vec<WeakPoint> = threads.iter().map(|&t| {
+            let n = Scaling::Fib.scale(arg, t);
+            WeakPoint {
+                threads: t,
+                arg: n,
+                units: fib_calls(n),
+                elapsed: stats::median(&job_samples(t, samples, pin, FIB, || n).times[..]),
+            }
+        }).collect();
+        print_weak(&format!("fib_{}", arg), Scaling::Fib, "calls", samples, &points);
+    }
+}
+
+fn weak_sort<Ret: Send + Sync>(samples: usize, pin: &[usize], name: &str, fun: fn(&mut [usize]) -> TaskResult<&mut [usize], Ret>, join: fn(&[Ret]) -> Ret, args: &[usize], threads: &[usize]) {
+    for &arg in args {
+        if sort_units(arg) == 0 {
+            // An empty input has no work to scale, and the time per unit would divide by zero
+            println!("Skipping weak scaling {}_{}, the input is empty\n", name, arg);
+            continue;
+        }
+        let points: Vec<WeakPoint> = threads.iter().map(|&t| {
+            let size = Scaling::Linear.scale(arg, t);
+            WeakPoint {
+                threads: t,
+                arg: size,
+                units: sort_units(size),
+                elapsed: stats::median(&sort_job_samples(t, samples, pin, Policy::Local, fun, join, size).times[..]),
+            }
+        }).collect();
+        print_weak(&format!("{}_{}", name, arg), Scaling::Linear, "n*log2(n)", samples, &points);
+    }
+}
+
+fn weak_sumtree(samples: usize, pin: &[usize], shape: &str, gen: fn(usize) -> Tree, scaling: Scaling, args: &[usize], works: &[(usize, usize)], threads: &[usize]) {
+    for &arg in args {
+        for &(work, spins) in works {
+            let points: Vec<WeakPoint> = threads.iter().map(|&t| {
+                let depth = scaling.scale(arg, t);
+                let mut tree = gen(depth);
+                set_work(&mut tree, spins);
+                WeakPoint {
+                    threads: t,
+                    arg: depth,
+                    units: tree.size() as u64,
+                    elapsed: stats::median(&sumtree_job_samples(t, samples, pin, &tree).times[..]),
+                }
+            }).collect();
+            print_weak(&sumtree_name(shape, Layout::Nested, arg, work), scaling, "nodes", samples, &points);
+        }
+    }
+}
+
+/// Job times of sorting `size` random elements placed according to `numa`, with the data
+/// regenerated outside the timing.
+fn sort_job_samples<Ret: Send + Sync>(threads: usize, samples: usize, pin: &[usize], numa: Policy, fun: fn(&mut [usize]) -> TaskResult<&mut [usize], Ret>, join: fn(&[Ret]) -> Ret, size: usize) -> Samples {
+    let mut data = numa::create_buffer(numa, size, threads, pin);
+    let result = {
+        let data_ptr = data.as_mut_ptr();
+        job_samples(threads, samples, pin, Algorithm {
+            fun: fun,
+            style: AlgoStyle::Reduce(ReduceStyle::NoArg(join)),
+        }, || {
+            let d = unsafe { slice::from_raw_parts_mut(data_ptr, size) };
+            create_vec_rnd(893475343, d);
+            d
+        })
+    };
+    verify_sorted(&data[..]);
+    result
+}
+
+/// Times of sorting `size` random elements sequentially with `sort`, like `sort_job_samples`.
+fn sort_seq_samples(samples: usize, sort: fn(&mut [usize]), size: usize) -> Samples {
+    let mut data: Vec<usize> = vec![0; size];
+    let result = {
+        let data_ptr = data.as_mut_ptr();
+        seq_samples(samples, || {
+            let d = unsafe { slice::from_raw_parts_mut(data_ptr, size) };
+            create_vec_rnd(893475343, d);
+            d
+        }, sort)
+    };
+    verify_sorted(&data[..]);
+    result
+}
+
+fn sumtree_job_samples(threads: usize, samples: usize, pin: &[usize], tree: &Tree) -> Samples {
+    job_samples(threads, samples, pin, Algorithm {
+        fun: sum_tree_task,
+        style: AlgoStyle::Reduce(ReduceStyle::Arg(sum_tree_join)),
+    }, || tree)
+}
+
+/// Efficiency is relative to the first thread count, 100% means the time per unit of work
+/// per thread stayed the same as the input grew.
+fn print_weak(name: &str, scaling: Scaling, unit: &str, samples: usize, points: &[WeakPoint]) {
+    println!("Weak scaling {}, input {}, median of {} samples", name, scaling.describe(), samples);
+    println!("{:>8}{:>12}{:>14}{:>14}{:>20}{:>12}", "threads", "input", unit, "time", "time/unit/thread", "efficiency");
+    let base = points.first().map(|p| p.ns_per_unit_per_thread()).unwrap_or(0.0);
+    for p in points {
+        println!("{:>8}{:>12}{:>14}{:>14}{:>20}{:>12}", format!("T{}", p.threads), p.arg, p.units, format(p.elapsed),
+            format!("{:.3} ns", p.ns_per_unit_per_thread()),
+            format!("{:.1}%", 100.0 * base / p.ns_per_unit_per_thread()));
+    }
+    println!("");
+}
+
+fn tree_workloads(function: &str, fib_args: &[usize], sort_args: &[usize], nqueens_args: &[usize], sumtree_args: &[usize], sumtree_work: &[(usize, usize)]) {
+    let sumtree = |shape: &str, gen: fn(usize) -> Tree| {
+        for &arg in sumtree_args {
+            for &(work, spins) in sumtree_work {
+                let mut tree = gen(arg);
+                set_work(&mut tree, spins);
+                print_treestats(&sumtree_name(shape, Layout::Nested, arg, work), &treestats::walk(sum_tree_task, &tree));
+            }
+        }
+    };
+    match function {
+        "fib" => for &arg in fib_args {
+            print_treestats(&format!("fib_{}", arg), &treestats::walk(FIB.fun, arg));
+        },
+        "qsort" | "mergesort" => for &arg in sort_args {
+            let mut data: Vec<usize> = vec![0; arg];
+            create_vec_rnd(893475343, &mut data[..]);
+            let stats = if function == "qsort" {
+                treestats::walk(quicksort_task, &mut data[..])
+            } else {
+                treestats::walk(mergesort_task, &mut data[..])
+            };
+            print_treestats(&format!("{}_{}", function, arg), &stats);
+        },
+        "nqueens_reduce" => for &arg in nqueens_args {
+            print_treestats(&format!("nqueens_reduce_{}", arg), &treestats::walk(NQUEENS_REDUCE.fun, (vec![], arg)));
+        },
+        "nqueens_search" => for &arg in nqueens_args {
+            print_treestats(&format!("nqueens_search_{}", arg), &treestats::walk(NQUEENS_SEARCH.fun, (vec![], arg)));
+        },
+        "sumtree_unbalanced" => sumtree("sumtree_unbalanced", gen_unbalanced_tree),
+        "sumtree_list" => sumtree("sumtree_listtree", gen_list_tree),
+        "sumtree_balanced" => sumtree("sumtree_balanced", gen_balanced_tree),
+        other => println!("Task tree statistics are not supported for: {}", other),
+    }
+}
+
+fn print_treestats(name: &str, tree: &TreeStats) {
+    println!("Task tree {}: {} tasks, {} leaves, depth {}, mean fan-out {:.2}", name, tree.tasks, tree.leaves(),
+        tree.depths.len() - 1, tree.mean_fanout());
+    println!("{:>10}{:>12}", "depth", "tasks");
+    if tree.depths.len() <= 64 {
+        for (depth, &count) in tree.depths.iter().enumerate() {
+            println!("{:>10}{:>12}", depth, count);
+        }
+    } else {
+        // Too deep to list, sum the depths into power of two buckets
+        let mut bucket = (0, 1);
+        while bucket.0 < tree.depths.len() {
+            let end = cmp::min(bucket.1, tree.depths.len());
+            println!("{:>10}{:>12}", format!("< {}", end), tree.depths[bucket.0..end].iter().fold(0, |acc, &c| acc + c));
+            bucket = (end, end * 2);
+        }
+    }
+    println!("{:>10}{:>12}", "fan-out", "tasks");
+    for (fanout, count) in tree.fanouts.iter() {
+        println!("{:>10}{:>12}", fanout, count);
+    }
+    let leaves = &tree.leaf_times[..];
+    if !leaves.is_empty() {
+        println!("Leaf work: total {}, min {}, p50 {}, p90 {}, p99 {}, max {}", format(leaves.iter().fold(0, |acc, &t| acc + t)),
+            format(leaves[0]), format(stats::percentile(leaves, 50.0)), format(stats::percentile(leaves, 90.0)),
+            format(stats::percentile(leaves, 99.0)), format(leaves[leaves.len() - 1]));
+        for (upper, count) in stats::log2_histogram(leaves) {
+            println!("{:>14} {:>10}", format!("< {}", format(upper)), count);
+        }
+    }
+    println!("");
+}
+
+/// Analyzes every argument of `function` and measures it on every thread count.
+fn span_workloads(function: &str, samples: usize, pin: &[usize], threads: &[usize], fib_args: &[usize], sort_args: &[usize], nqueens_args: &[usize], sumtree_args: &[usize], sumtree_work: &[(usize, usize)]) {
+    let measure = |name: &str, analysis: Analysis, measure: &Fn(usize) -> Samples| {
+        let measured: Vec<(usize, u64)> = threads.iter().map(|&t| (t, stats::median(&measure(t).times[..]))).collect();
+        print_span(name, samples, analysis, &measured);
+    };
+    let sumtree = |shape: &str, gen: fn(usize) -> Tree| {
+        for &arg in sumtree_args {
+            for &(work, spins) in sumtree_work {
+                let mut tree = gen(arg);
+                set_work(&mut tree, spins);
+                let algorithm = Algorithm {
+                    fun: sum_tree_task,
+                    style: AlgoStyle::Reduce(ReduceStyle::Arg(sum_tree_join)),
+                };
+                let analysis = span::analyze(&algorithm, &tree);
+                measure(&sumtree_name(shape, Layout::Nested, arg, work), analysis, &|t| sumtree_job_samples(t, samples, pin, &tree));
+            }
+        }
+    };
+    match function {
+        "fib" => for &arg in fib_args {
+            measure(&format!("fib_{}", arg), span::analyze(&FIB, arg), &|t| job_samples(t, samples, pin, FIB, || arg));
+        },
+        "qsort" => for &arg in sort_args {
+            measure(&format!("qsort_{}", arg), sort_analysis(quicksort_task, quicksort_join, arg), &|t| sort_job_samples(t, samples, pin, Policy::Local, quicksort_task, quicksort_join, arg));
+        },
+        "mergesort" => for &arg in sort_args {
+            measure(&format!("mergesort_{}", arg), sort_analysis(mergesort_task, mergesort_join, arg), &|t| sort_job_samples(t, samples, pin, Policy::Local, mergesort_task, mergesort_join, arg));
+        },
+        "nqueens_reduce" => for &arg in nqueens_args {
+            measure(&format!("nqueens_reduce_{}", arg), span::analyze(&NQUEENS_REDUCE, (vec![], arg)), &|t| job_samples(t, samples, pin, NQUEENS_REDUCE, || (vec![], arg)));
+        },
+        "sumtree_unbalanced" => sumtree("sumtree_unbalanced", gen_unbalanced_tree),
+        "sumtree_list" => sumtree("sumtree_listtree", gen_list_tree),
+        "sumtree_balanced" => sumtree("sumtree_balanced", gen_balanced_tree),
+        other => println!("Work/span analysis is not supported for: {}", other),
+    }
+}
+
+fn sort_analysis<Ret>(fun: fn(&mut [usize]) -> TaskResult<&mut [usize], Ret>, join: fn(&[Ret]) -> Ret, size: usize) -> Analysis {
+    let mut data: Vec<usize> = vec![0; size];
+    create_vec_rnd(893475343, &mut data[..]);
+    let analysis = span::analyze(&Algorithm {
+        fun: fun,
+        style: AlgoStyle::Reduce(ReduceStyle::NoArg(join)),
+    }, &mut data[..]);
+    verify_sorted(&data[..]);
+    analysis
+}
+
+/// Measured speedups are relative to T1, so they include the scheduling overhead that
+/// the sequential execution does not have.
+fn print_span(name: &str, samples: usize, analysis: Analysis, measured: &[(usize, u64)]) {
+    println!("Work/span {}, {} tasks, T1 {}, T\u{221e} {}, parallelism {:.1}", name, analysis.tasks,
+        format(analysis.work), format(analysis.span), analysis.parallelism());
+    println!("{:>8}{:>14}{:>10}{:>14}{:>14}", "threads", "median", "speedup", "greedy bound", "max speedup");
+    for &(t, elapsed) in measured {
+        println!("{:>8}{:>14}{:>10.2}{:>14.2}{:>14.2}", format!("T{}", t), format(elapsed),
+            analysis.work as f64 / elapsed as f64, analysis.greedy_speedup(t), analysis.max_speedup(t));
+    }
+    println!("Median of {} samples", samples);
+    println!("");
+}
+
+/// Runs one traced job of `function` with its first argument and writes the timeline
+/// to `dir`/<workload>_T<threads>.json.
+fn trace_job(function: &str, dir: &Path, pin: &[usize], threads: usize, fib_arg: usize, sort_arg: usize, sumtree_arg: usize, (sumtree_work, sumtree_spins): (usize, usize)) {
+    let (workload, result) = match function {
+        "fib" => (format!("fib_{}", fib_arg), job_samples(threads, 1, pin, Algorithm {
+            fun: trace::traced_fib_task,
+            style: AlgoStyle::Reduce(ReduceStyle::NoArg(trace::traced_fib_join)),
+        }, || fib_arg)),
+        "qsort" => (format!("qsort_{}", sort_arg), sort_job_samples(threads, 1, pin, Policy::Local, trace::traced_quicksort_task, trace::traced_quicksort_join, sort_arg)),
+        "mergesort" => (format!("mergesort_{}", sort_arg), sort_job_samples(threads, 1, pin, Policy::Local, trace::traced_mergesort_task, trace::traced_mergesort_join, sort_arg)),
+        "sumtree_unbalanced" | "sumtree_list" | "sumtree_balanced" => {
+            let (shape, gen): (&str, fn(usize) -> Tree) = match function {
+                "sumtree_unbalanced" => ("sumtree_unbalanced", gen_unbalanced_tree),
+                "sumtree_list" => ("sumtree_listtree", gen_list_tree),
+                _ => ("sumtree_balanced", gen_balanced_tree),
+            };
+            let mut tree = gen(sumtree_arg);
+            set_work(&mut tree, sumtree_spins);
+            let result = job_samples(threads, 1, pin, Algorithm {
+                fun: trace::traced_sum_tree_task,
+                style: AlgoStyle::Reduce(ReduceStyle::Arg(trace::traced_sum_tree_join)),
+            }, || &tree);
+            (sumtree_name(shape, Layout::Nested, sumtree_arg, sumtree_work), result)
+        },
+        other => {
+            println!("Tracing is not supported for: {}", other);
+            return;
+        },
+    };
+    let events = trace::take();
+    let path = dir.join(format!("{}_T{}.json", workload, threads));
+    trace::write_chrome(&path, &events).unwrap_or_else(|e| panic!("Could not write trace {}: {}", path.display(), e));
+    println!("Traced {} on {} threads: {} task and join calls on {} threads in {}, written to {}", workload, threads,
+        events.iter().fold(0, |acc, t| acc + t.events.len()), events.len(), format(result.times[0]), path.display());
+}
+
+/// Job times of the ForkJoin implementation of `function` for every argument and thread
+/// count, for the sorts also of every NUMA policy and for sumtree of every cutoff. Unless
+/// `seq` is false also of the sequential implementation, as the row "seq".
+fn baseline_rows(function: &str, samples: usize, pin: &[usize], seq: bool, threads: &[usize], numa_policies: &[Policy], fib_args: &[usize], sort_args: &[usize], nqueens_args: &[usize], sumtree_args: &[usize], sumtree_work: &[(usize, usize)], sumtree_cutoffs: &[Cutoff]) -> Vec<Row> {
+    let row = |workload: &str, implementation: String, result: Samples| Row {
+        workload: workload.to_string(),
+        implementation: implementation,
+        samples: result.times,
+        counters: result.counters,
+        allocs: result.allocs,
+        threadstats: result.threadstats,
+        pinned: result.pinned,
+    };
+    let variant_rows = |workload: &str, variant: &str, measure: &Fn(usize) -> Samples| -> Vec<Row> {
+        threads.iter().map(|&t| row(workload, format!("T{}{}", t, variant), measure(t))).collect()
+    };
+    let seq_rows = |workload: &str, measure: &Fn() -> Samples| -> Vec<Row> {
+        if seq { vec![row(workload, "seq".to_string(), measure())] } else { vec![] }
+    };
+    let rows = |workload: String, seq_measure: &Fn() -> Samples, measure: &Fn(usize) -> Samples| -> Vec<Row> {
+        let mut all = seq_rows(&workload, seq_measure);
+        all.extend(variant_rows(&workload, "", measure));
+        all
+    };
+    let sumtree = |shape: &str, gen: fn(usize) -> Tree| -> Vec<Row> {
+        let mut all = vec![];
+        for &arg in sumtree_args {
+            for &(work, spins) in sumtree_work {
+                let mut tree = gen(arg);
+                set_work(&mut tree, spins);
+                let workload = sumtree_name(shape, Layout::Nested, arg, work);
+                all.extend(seq_rows(&workload, &|| seq_samples(samples, || &tree, sum_tree_seq)));
+                all.extend(variant_rows(&workload, "", &|t| sumtree_job_samples(t, samples, pin, &tree)));
+                for &cutoff in sumtree_cutoffs {
+                    all.extend(variant_rows(&workload, &format!("_{}", cutoff.name()), &|t| job_samples(t, samples, pin, Algorithm {
+                        fun: sum_tree_task_cutoff,
+                        style: AlgoStyle::Reduce(ReduceStyle::Arg(sum_tree_join)),
+                    }, || (&tree, 0, cutoff))));
+                }
+            }
         }
+        all
+    };
+    match function {
+        "fib" => fib_args.iter().flat_map(|&arg| rows(format!("fib_{}", arg),
+            &|| seq_samples(samples, || arg, fib),
+            &|t| job_samples(t, samples, pin, FIB, || arg))).collect(),
+        "qsort" => sort_args.iter().flat_map(|&arg| {
+            let workload = format!("qsort_{}", arg);
+            let mut all = seq_rows(&workload, &|| sort_seq_samples(samples, quicksort_seq, arg));
+            for &numa in numa_policies {
+                all.extend(variant_rows(&workload, numa.suffix(), &|t| sort_job_samples(t, samples, pin, numa, quicksort_task, quicksort_join, arg)));
+            }
+            all
+        }).collect(),
+        "mergesort" => sort_args.iter().flat_map(|&arg| {
+            let workload = format!("mergesort_{}", arg);
+            let mut all = seq_rows(&workload, &|| sort_seq_samples(samples, mergesort_seq, arg));
+            for &numa in numa_policies {
+                all.extend(variant_rows(&workload, numa.suffix(), &|t| sort_job_samples(t, samples, pin, numa, mergesort_task, mergesort_join, arg)));
+            }
+            all
+        }).collect(),
+        "nqueens_reduce" => nqueens_args.iter().flat_map(|&arg| rows(format!("nqueens_reduce_{}", arg),
+            &|| seq_samples(samples, || (), |()| nqueens_reduce(&[], arg)),
+            &|t| job_samples(t, samples, pin, NQUEENS_REDUCE, || (vec![], arg)))).collect(),
+        "sumtree_unbalanced" => sumtree("sumtree_unbalanced", gen_unbalanced_tree),
+        "sumtree_list" => sumtree("sumtree_listtree", gen_list_tree),
+        "sumtree_balanced" => sumtree("sumtree_balanced", gen_balanced_tree),
+        other => {
+            println!("Baselines are not supported for: {}", other);
+            vec![]
+        },
+    }
+}
+
+/// Names of the workloads `baseline_rows` measures for `function`.
+fn baseline_workloads(function: &str, fib_args: &[usize], sort_args: &[usize], nqueens_args: &[usize], sumtree_args: &[usize], sumtree_work: &[(usize, usize)]) -> Vec<String> {
+    let sumtree = |shape: &str| -> Vec<String> {
+        sumtree_args.iter().flat_map(|&arg| sumtree_work.iter().map(move |&(work, _)| sumtree_name(shape, Layout::Nested, arg, work))).collect()
+    };
+    match function {
+        "fib" => fib_args.iter().map(|arg| format!("fib_{}", arg)).collect(),
+        "qsort" => sort_args.iter().map(|arg| format!("qsort_{}", arg)).collect(),
+        "mergesort" => sort_args.iter().map(|arg| format!("mergesort_{}", arg)).collect(),
+        "nqueens_reduce" => nqueens_args.iter().map(|arg| format!("nqueens_reduce_{}", arg)).collect(),
+        "sumtree_unbalanced" => sumtree("sumtree_unbalanced"),
+        "sumtree_list" => sumtree("sumtree_listtree"),
+        "sumtree_balanced" => sumtree("sumtree_balanced"),
+        _ => vec![],
+    }
+}
+
+fn print_counters(rows: &[Row]) {
+    if rows.iter().all(|r| r.counters.iter().all(|c| c.is_none())) {
+        println!("Hardware counters are not available, build with --features perf-counters on Linux and check /proc/sys/kernel/perf_event_paranoid");
         println!("");
+        return;
+    }
+    // Opened the same way as for the measurements, so the mode is the same too
+    let mode = perf::Counters::open().map(|c| c.mode()).unwrap_or("in an unknown mode");
+    println!("Average event counts per job, counting {}", mode);
+    print!("{:<32}{:>8}{:>12}", "workload", "impl", "time");
+    for event in perf::EVENTS.iter() {
+        print!("{:>18}", event);
+    }
+    println!("{:>8}", "IPC");
+    for row in rows {
+        print!("{:<32}{:>8}{:>12}", row.workload, row.implementation, format(stats::median(&row.samples[..])));
+        for count in row.counters.iter() {
+            match *count {
+                Some(c) => print!("{:>18.0}", c),
+                None => print!("{:>18}", "-"),
+            }
+        }
+        match (row.counters[0], row.counters[1]) {
+            (Some(cycles), Some(instructions)) if cycles > 0.0 => println!("{:>8.2}", instructions / cycles),
+            _ => println!("{:>8}", "-"),
+        }
+    }
+    println!("");
+}
+
+fn print_allocations(rows: &[Row]) {
+    if rows.iter().all(|r| r.allocs.is_none()) {
+        println!("Allocations are not counted, build with --features count-allocations");
+        println!("");
+        return;
+    }
+    println!("Allocations per job, measured on the {} allocator", allocs::allocator());
+    println!("{:<32}{:>8}{:>12}{:>14}{:>14}{:>14}", "workload", "impl", "time", "allocations", "bytes", "max peak");
+    for row in rows {
+        if let Some(allocs) = row.allocs {
+            println!("{:<32}{:>8}{:>12}{:>14.1}{:>14.0}{:>14}", row.workload, row.implementation, format(stats::median(&row.samples[..])), allocs.allocations, allocs.bytes, allocs.peak);
+        }
     }
     println!("");
 }
+
+/// Worker statistics summed over the workers of every thread count, per job.
+fn print_threadstats(rows: &[Row]) {
+    println!("Worker statistics per job");
+    print_threadstats_header(&format!("{:<32}{:>8}", "workload", "impl"));
+    for row in rows {
+        if let Some(ref s) = row.threadstats {
+            print_threadstats_row(&format!("{:<32}{:>8}", row.workload, row.implementation), stats::median(&row.samples[..]), s);
+        }
+    }
+    println!("");
+}
+
+fn print_threadstats_header(name: &str) {
+    println!("{:>40}{:>12}{:>12}{:>12}{:>14}{:>12}{:>10}", name, "time", "tasks", "steals", "failed steals", "idle", "busiest");
+}
+
+fn print_threadstats_row(name: &str, time: u64, s: &PoolStats) {
+    println!("{:>40}{:>12}{:>12.0}{:>12.1}{:>14.1}{:>12}{:>10}", name, format(time),
+        s.tasks(), s.steals(), s.failed_steals(), format((s.idle_us() * 1000.0) as u64), format!("{:.1}%", s.busiest_share() * 100.0));
+}
+
+/// Prints the median of every row of the first baseline next to the same row in the
+/// others, with the change relative to the first. Changes the Mann-Whitney U test finds
+/// significant are marked with a *.
+fn print_allocator_report(names: &[String], baselines: &[Vec<Row>], workloads: &[String]) {
+    println!("Allocator comparison, relative to {}", names[0]);
+    print!("{:<32}{:>8}", "workload", "impl");
+    for name in names {
+        print!("{:>24}", name);
+    }
+    println!("");
+    for row in baselines[0].iter().filter(|r| workloads.contains(&r.workload)) {
+        let first = stats::median(&row.samples[..]);
+        print!("{:<32}{:>8}{:>24}", row.workload, row.implementation, format(first));
+        for other in baselines[1..].iter() {
+            match other.iter().find(|o| o.workload == row.workload && o.implementation == row.implementation) {
+                Some(o) => {
+                    let median = stats::median(&o.samples[..]);
+                    let significant = baseline::mann_whitney_u(&row.samples[..], &o.samples[..]) < baseline::ALPHA;
+                    let cell = format!("{} ({:+.1}%){}", format(median), (median as f64 / first as f64 - 1.0) * 100.0, if significant { "*" } else { " " });
+                    print!("{:>24}", cell);
+                }
+                None => print!("{:>24}", "-"),
+            }
+        }
+        println!("");
+    }
+    println!("");
+}
+
+/// Prints how every row changed since the baseline. Returns true if anything regressed.
+fn compare_baseline(name: &str, old: &[Row], new: &[Row]) -> bool {
+    println!("Comparison with baseline {} (Mann-Whitney U, alpha {}, changes below {}% ignored)", name, baseline::ALPHA, baseline::MIN_CHANGE * 100.0);
+    println!("{:<32}{:>8}{:>14}{:>14}{:>10}{:>10}  {}", "workload", "impl", "baseline", "now", "change", "p", "verdict");
+    let comparisons = baseline::compare(old, new);
+    for c in comparisons.iter() {
+        println!("{:<32}{:>8}{:>14}{:>14}{:>10}{:>10.4}  {:?}", c.workload, c.implementation, format(c.old_median), format(c.new_median),
+            format!("{:+.1}%", c.change() * 100.0), c.p_value, c.verdict);
+    }
+    if comparisons.len() < new.len() {
+        println!("{} results have no counterpart in the baseline", new.len() - comparisons.len());
+    }
+    println!("");
+    comparisons.iter().any(|c| c.verdict == Verdict::Regression)
+}
+
+fn openloop_fib(args: &[usize], threads: &[usize], receivers: usize, arrival: Arrival, rates: &[f64], jobs: usize) {
+    for &arg in args {
+        for &rate in rates {
+            println!("Open loop fib({}), {:?} arrivals at {} jobs/s, {} jobs, {} receivers", arg, arrival, rate, jobs, receivers);
+            for &t in threads {
+                let result = fib_open_loop(t, receivers, arrival, rate, jobs, arg);
+                let mut corrected = result.corrected;
+                let mut uncorrected = result.uncorrected;
+                corrected.sort();
+                uncorrected.sort();
+
+                println!("T{}, {} of {} jobs submitted more than 1 ms late", t, result.late, jobs);
+                println!("{:>14}{:>12}{:>12}{:>12}{:>12}{:>12}", "latency", "p50", "p90", "p99", "p99.9", "max");
+                for &(name, latencies) in [("corrected", &corrected), ("uncorrected", &uncorrected)].iter() {
+                    if latencies.is_empty() {
+                        continue;
+                    }
+                    println!("{:>14}{:>12}{:>12}{:>12}{:>12}{:>12}", name,
+                        format(stats::percentile(&latencies[..], 50.0)),
+                        format(stats::percentile(&latencies[..], 90.0)),
+                        format(stats::percentile(&latencies[..], 99.0)),
+                        format(stats::percentile(&latencies[..], 99.9)),
+                        format(latencies[latencies.len() - 1]));
+                }
+                println!("Corrected latency histogram:");
+                for (upper, count) in stats::log2_histogram(&corrected[..]) {
+                    println!("{:>14} {:>8}", format!("< {}", format(upper)), count);
+                }
+                println!("");
+            }
+        }
+    }
+}
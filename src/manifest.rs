@@ -0,0 +1,160 @@
+use time;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use allocs;
+use cpus;
+
+/// The lock file this binary was built with, for the exact forkjoin revision.
+const CARGO_LOCK: &'static str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.lock"));
+
+/// Everything about the machine and build that can explain why two result sets differ.
+pub struct Manifest {
+    pub fields: Vec<(&'static str, Value)>,
+}
+
+pub enum Value {
+    Str(Option<String>),
+    Int(usize),
+    List(Vec<String>),
+}
+
+impl Manifest {
+    pub fn collect() -> Manifest {
+        let features: Vec<String> = [
+            ("threadstats", cfg!(feature = "threadstats")),
+            ("linux-affinity", cfg!(feature = "linux-affinity")),
+            ("perf-counters", cfg!(feature = "perf-counters")),
+            ("count-allocations", cfg!(feature = "count-allocations")),
+            ("system-alloc", cfg!(feature = "system-alloc")),
+            ("slab-alloc", cfg!(feature = "slab-alloc")),
+        ].iter().filter(|&&(_, on)| on).map(|&(name, _)| name.to_string()).collect();
+
+        Manifest {
+            fields: vec![
+                ("timestamp", Value::Str(Some(format!("{}", time::now_utc().rfc3339())))),
+                ("hostname", Value::Str(read_trimmed("/proc/sys/kernel/hostname"))),
+                ("cpu_model", Value::Str(cpus::model())),
+                ("packages", Value::Int(cpus::packages())),
+                ("physical_cores", Value::Int(cpus::physical())),
+                ("logical_cpus", Value::Int(cpus::logical())),
+                ("allowed_cpus", Value::List(cpus::allowed_cpus().unwrap_or(vec![]).iter().map(|c| c.to_string()).collect())),
+                ("numa_nodes", Value::List(numa_nodes())),
+                ("governors", Value::List(governors())),
+                ("memory", Value::Str(meminfo_total())),
+                ("kernel", Value::Str(read_trimmed("/proc/sys/kernel/osrelease"))),
+                ("rustc", Value::Str(build_value(env!("BUILD_RUSTC")))),
+                ("commit", Value::Str(build_value(env!("BUILD_COMMIT")))),
+                ("dirty", Value::Str(build_value(env!("BUILD_DIRTY")))),
+                ("forkjoin_source", Value::Str(lock_source("forkjoin"))),
+                ("criterion_source", Value::Str(lock_source("criterion"))),
+                ("features", Value::List(features)),
+                ("allocator", Value::Str(Some(allocs::allocator().to_string()))),
+                ("args", Value::List(env::args().collect())),
+            ],
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let fields: Vec<String> = self.fields.iter().map(|&(key, ref value)| {
+            let value = match *value {
+                Value::Str(Some(ref s)) => json_string(s),
+                Value::Str(None) => "null".to_string(),
+                Value::Int(i) => i.to_string(),
+                Value::List(ref l) => format!("[{}]", l.iter().map(|s| json_string(s)).collect::<Vec<_>>().join(", ")),
+            };
+            format!("  {}: {}", json_string(key), value)
+        }).collect();
+        format!("{{\n{}\n}}\n", fields.join(",\n"))
+    }
+
+    /// Writes the manifest to `dir`/manifest.json, creating `dir` if needed.
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        try!(fs::create_dir_all(dir));
+        let mut file = try!(File::create(dir.join("manifest.json")));
+        file.write_all(self.to_json().as_bytes())
+    }
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    cpus::read_file(path).map(|s| s.trim().to_string())
+}
+
+/// A value build.rs recorded, which is empty if it couldn't find out.
+fn build_value(value: &str) -> Option<String> {
+    if value.is_empty() { None } else { Some(value.to_string()) }
+}
+
+/// The CPUs of every NUMA node, as "node0: 0-7".
+fn numa_nodes() -> Vec<String> {
+    let mut nodes: Vec<(usize, String)> = match fs::read_dir("/sys/devices/system/node") {
+        Ok(entries) => entries.filter_map(|e| e.ok()).filter_map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            if !name.starts_with("node") {
+                return None;
+            }
+            name[4..].parse::<usize>().ok().map(|n| {
+                let cpulist = read_trimmed(&format!("/sys/devices/system/node/{}/cpulist", name)).unwrap_or(String::new());
+                (n, format!("{}: {}", name, cpulist))
+            })
+        }).collect(),
+        Err(_) => vec![],
+    };
+    nodes.sort();
+    nodes.into_iter().map(|(_, node)| node).collect()
+}
+
+/// The distinct frequency governors of the allowed CPUs.
+fn governors() -> Vec<String> {
+    let mut governors: Vec<String> = cpus::allowed_cpus().unwrap_or(vec![]).iter()
+        .filter_map(|c| read_trimmed(&format!("/sys/devices/system/cpu/cpu{}/cpufreq/scaling_governor", c)))
+        .collect();
+    governors.sort();
+    governors.dedup();
+    governors
+}
+
+fn meminfo_total() -> Option<String> {
+    cpus::read_file("/proc/meminfo").and_then(|meminfo| {
+        let total = meminfo.lines()
+            .find(|line| line.starts_with("MemTotal:"))
+            .map(|line| line["MemTotal:".len()..].trim().to_string());
+        total
+    })
+}
+
+/// The `source` of a package in Cargo.lock, for git dependencies "git+<url>#<commit>".
+fn lock_source(package: &str) -> Option<String> {
+    let name = format!("name = \"{}\"", package);
+    CARGO_LOCK.lines()
+        .skip_while(|line| line.trim() != name)
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty() && !line.starts_with('['))
+        .find(|line| line.starts_with("source = "))
+        .map(|line| line["source = ".len()..].trim_matches('"').to_string())
+}
+
+#[test]
+fn test_json_string() {
+    assert_eq!("\"a\\\"b\\\\c\\n\"", json_string("a\"b\\c\n"));
+}
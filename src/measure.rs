@@ -0,0 +1,99 @@
+use forkjoin::{ForkPool,Algorithm};
+use test;
+use time;
+
+use allocs::{self, Allocs, Tracker};
+use perf::{self, Counters};
+use pin;
+use threadstats::{self, PoolStats};
+
+/// Job times in ns and, when built with `perf-counters`, `count-allocations` or
+/// `threadstats`, the average event counts and allocations per job and worker statistics.
+pub struct Samples {
+    pub times: Vec<u64>,
+    /// One entry per `perf::EVENTS`, None where not available
+    pub counters: Vec<Option<f64>>,
+    pub allocs: Option<Allocs>,
+    pub threadstats: Option<PoolStats>,
+    /// Thread id and CPU of every pinned worker
+    pub pinned: Vec<(i32, usize)>,
+}
+
+/// Runs `samples` jobs of `algorithm` on one pool and returns the time of every job,
+/// from schedule until the result is received, in ns. `setup` creates the argument
+/// of every job and is not timed or counted. The workers are pinned to `pin`, unless empty.
+pub fn job_samples<Arg, Ret, F>(threads: usize, samples: usize, pin: &[usize], algorithm: Algorithm<Arg, Ret>, mut setup: F) -> Samples where
+    Arg: Send,
+    Ret: Send + Sync,
+    F: FnMut() -> Arg
+{
+    // Opened before the pool so the worker threads are counted too
+    let counters = Counters::open();
+    let (forkpool, pinned) = pin::pinned(pin, threads, || ForkPool::with_threads(threads));
+    let pool = forkpool.init_algorithm(algorithm);
+
+    let mut times = Vec::with_capacity(samples);
+    let mut deltas = Vec::with_capacity(samples);
+    let mut job_allocs = Vec::with_capacity(samples);
+    let mut pool_stats = None;
+    for _ in 0..samples {
+        let arg = setup();
+        let workers_before = threadstats::snapshot(&forkpool);
+        let tracker = Tracker::start();
+        let before = counters.as_ref().map(|c| c.read());
+        let start = time::precise_time_ns();
+        let job = pool.schedule(arg);
+        job.recv().unwrap();
+        times.push(time::precise_time_ns() - start);
+        if let Some(tracker) = tracker {
+            job_allocs.push(tracker.finish());
+        }
+        if let (Some(c), Some(before)) = (counters.as_ref(), before) {
+            deltas.push(perf::delta(&before, &c.read()));
+        }
+        if let (Some(before), Some(after)) = (workers_before, threadstats::snapshot(&forkpool)) {
+            threadstats::accumulate(&mut pool_stats, threadstats::delta(&before, &after, 1));
+        }
+    }
+    Samples {
+        times: times,
+        counters: perf::average(&deltas[..]),
+        allocs: allocs::average(&job_allocs[..]),
+        threadstats: pool_stats,
+        pinned: pinned,
+    }
+}
+
+/// Like `job_samples` for a sequential implementation, `run` is called on the calling
+/// thread with the argument `setup` creates and timed `samples` times.
+pub fn seq_samples<Arg, Ret, S, F>(samples: usize, mut setup: S, mut run: F) -> Samples where
+    S: FnMut() -> Arg,
+    F: FnMut(Arg) -> Ret
+{
+    let counters = Counters::open();
+
+    let mut times = Vec::with_capacity(samples);
+    let mut deltas = Vec::with_capacity(samples);
+    let mut job_allocs = Vec::with_capacity(samples);
+    for _ in 0..samples {
+        let arg = setup();
+        let tracker = Tracker::start();
+        let before = counters.as_ref().map(|c| c.read());
+        let start = time::precise_time_ns();
+        test::black_box(run(arg));
+        times.push(time::precise_time_ns() - start);
+        if let Some(tracker) = tracker {
+            job_allocs.push(tracker.finish());
+        }
+        if let (Some(c), Some(before)) = (counters.as_ref(), before) {
+            deltas.push(perf::delta(&before, &c.read()));
+        }
+    }
+    Samples {
+        times: times,
+        counters: perf::average(&deltas[..]),
+        allocs: allocs::average(&job_allocs[..]),
+        threadstats: None,
+        pinned: vec![],
+    }
+}
@@ -4,10 +4,34 @@ use std::mem;
 use std::ptr::{self, Unique};
 use std::slice;
 
+use backend::Join;
 use sortutils::verify_sorted;
+use spawnpool::{self, PoolMode, bench_pool};
+use numa::{self, Policy};
 use quicksort::quicksort_seq;
 
-pub fn par_mergesort<F>(b: &mut Bencher, threads: usize, size: usize, datafun: F) where
+pub fn par_mergesort<F>(b: &mut Bencher, threads: usize, mode: PoolMode, numa: Policy, size: usize, datafun: F) where
+    F: Fn(&mut [usize])
+{
+    let mut data = numa::create_buffer(numa, size, threads, &spawnpool::worker_cpus());
+    let data_ptr = data.as_mut_ptr();
+
+    bench_pool(b, threads, mode, || Algorithm {
+        fun: mergesort_task,
+        style: AlgoStyle::Reduce(ReduceStyle::NoArg(mergesort_join)),
+    }, || {
+        datafun(&mut data[..]);
+    }, |sortpool, ()| {
+        // A reused pool outlives every iteration, so the argument can't be a plain reborrow of data
+        let d = unsafe { slice::from_raw_parts_mut(data_ptr, size) };
+        let job = sortpool.schedule(d);
+        job.recv().unwrap()
+    }, |_| {
+        verify_sorted(unsafe { slice::from_raw_parts(data_ptr, size) });
+    });
+}
+
+pub fn seq_mergesort<F>(b: &mut Bencher, size: usize, datafun: F) where
     F: Fn(&mut [usize])
 {
     let mut data: Vec<usize> = (0..size).collect();
@@ -17,14 +41,8 @@ pub fn par_mergesort<F>(b: &mut Bencher, threads: usize, size: usize, datafun: F
     b.iter_with_setup_and_verify(|| {
         datafun(&mut data[..]);
     }, |()| {
-        let forkpool = ForkPool::with_threads(threads);
-        let sortpool = forkpool.init_algorithm(Algorithm {
-            fun: mergesort_task,
-            style: AlgoStyle::Reduce(ReduceStyle::NoArg(mergesort_join)),
-        });
-        let job = sortpool.schedule(&mut data_bench[..]);
-        job.recv().unwrap()
-    }, |_| {
+        mergesort_seq(&mut data_bench[..]);
+    }, |()| {
         verify_sorted(&mut data_verify[..]);
     });
 
@@ -32,7 +50,7 @@ pub fn par_mergesort<F>(b: &mut Bencher, threads: usize, size: usize, datafun: F
     mem::forget(data_verify);
 }
 
-pub fn seq_mergesort<F>(b: &mut Bencher, size: usize, datafun: F) where
+pub fn backend_mergesort<J: Join, F>(b: &mut Bencher, join: &J, size: usize, datafun: F) where
     F: Fn(&mut [usize])
 {
     let mut data: Vec<usize> = (0..size).collect();
@@ -42,7 +60,7 @@ pub fn seq_mergesort<F>(b: &mut Bencher, size: usize, datafun: F) where
     b.iter_with_setup_and_verify(|| {
         datafun(&mut data[..]);
     }, |()| {
-        mergesort_seq(&mut data_bench[..]);
+        mergesort_backend(join, 0, &mut data_bench[..]);
     }, |()| {
         verify_sorted(&mut data_verify[..]);
     });
@@ -61,7 +79,7 @@ pub fn par_mergesort_once(threads: usize, data: &mut [usize]) {
     job.recv().unwrap();
 }
 
-fn mergesort_task(d: &mut [usize]) -> TaskResult<&mut [usize], (Unique<usize>, usize)> {
+pub fn mergesort_task(d: &mut [usize]) -> TaskResult<&mut [usize], (Unique<usize>, usize)> {
     let len = d.len();
     if len <= 1000 {
         quicksort_seq(d);
@@ -72,7 +90,21 @@ fn mergesort_task(d: &mut [usize]) -> TaskResult<&mut [usize], (Unique<usize>, u
     }
 }
 
-fn mergesort_join(xs: &[(Unique<usize>, usize)]) -> (Unique<usize>, usize) {
+fn mergesort_backend<J: Join>(join: &J, depth: usize, d: &mut [usize]) {
+    let len = d.len();
+    if len <= 1000 {
+        quicksort_seq(d);
+    } else {
+        let (low, high) = d.split_at_mut(len / 2);
+        {
+            let tasks = vec![&mut *low, &mut *high].into_iter().map(|d| move || mergesort_backend(join, depth + 1, d)).collect();
+            join.join_all(depth, tasks);
+        }
+        merge(low, high);
+    }
+}
+
+pub fn mergesort_join(xs: &[(Unique<usize>, usize)]) -> (Unique<usize>, usize) {
     assert_eq!(2, xs.len());
     let (ref lowp, lowl) = xs[0];
     let (ref highp, highl) = xs[1];
@@ -103,7 +135,7 @@ fn test_mergesort_join() {
     assert_eq!(xs.len(), result_len);
 }
 
-fn mergesort_seq(d: &mut [usize]) {
+pub fn mergesort_seq(d: &mut [usize]) {
     let len = d.len();
     if len < 1000 {
         quicksort_seq(d);
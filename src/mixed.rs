@@ -0,0 +1,146 @@
+use forkjoin::{TaskResult,ForkPool,AlgoStyle,ReduceStyle,Algorithm};
+use thread_scoped;
+use time;
+
+use std::slice;
+use std::sync::{Barrier, Mutex};
+
+use fib::{fib_task, fib_join};
+use quicksort::quicksort_task;
+use sortutils::{create_vec_rnd, verify_sorted};
+use stats;
+use sumtree::{Tree, sum_tree_task, sum_tree_join};
+use throughput::Shared;
+
+/// A `ForkPool` only takes one argument and return type, so every algorithm
+/// sharing the pool wraps its own argument in this.
+pub enum MixedArg<'a> {
+    Fib(usize),
+    Sum(&'a Tree),
+    Sort(&'a mut [usize]),
+}
+
+pub const NAMES: [&'static str; 3] = ["fib", "sumtree", "qsort"];
+
+/// Median latencies of one algorithm when running alone on the pool and when
+/// running at the same time as the other algorithms.
+pub struct Interference {
+    pub name: &'static str,
+    pub solo: u64,
+    pub mixed: u64,
+}
+
+impl Interference {
+    pub fn slowdown(&self) -> f64 {
+        self.mixed as f64 / self.solo as f64
+    }
+}
+
+/// Initializes fib, sumtree and qsort on one pool. First runs each of them alone
+/// `rounds` times, then `rounds` times with one job of each scheduled at once
+/// from separate client threads.
+pub fn mixed_workload(threads: usize, rounds: usize, fib_n: usize, tree: &Tree, sort_size: usize) -> Vec<Interference> {
+    let forkpool = ForkPool::with_threads(threads);
+    let pools = Shared([
+        forkpool.init_algorithm(Algorithm {
+            fun: mixed_fib_task,
+            style: AlgoStyle::Reduce(ReduceStyle::NoArg(fib_join)),
+        }),
+        forkpool.init_algorithm(Algorithm {
+            fun: mixed_sum_task,
+            style: AlgoStyle::Reduce(ReduceStyle::Arg(sum_tree_join)),
+        }),
+        forkpool.init_algorithm(Algorithm {
+            fun: mixed_sort_task,
+            style: AlgoStyle::Reduce(ReduceStyle::NoArg(mixed_sort_join)),
+        }),
+    ]);
+    let lock = Mutex::new(());
+
+    let mut data: Vec<usize> = vec![0; sort_size];
+    let data_ptr = Shared(data.as_mut_ptr());
+
+    let run = |algo: usize| -> u64 {
+        let arg = match algo {
+            0 => MixedArg::Fib(fib_n),
+            1 => MixedArg::Sum(tree),
+            _ => MixedArg::Sort(unsafe { slice::from_raw_parts_mut(data_ptr.0, sort_size) }),
+        };
+        let start = time::precise_time_ns();
+        let job = {
+            let _guard = lock.lock().unwrap();
+            pools.0[algo].schedule(arg)
+        };
+        job.recv().unwrap();
+        time::precise_time_ns() - start
+    };
+    let run = &run;
+
+    let mut solo: Vec<Vec<u64>> = vec![vec![]; NAMES.len()];
+    let mut mixed: Vec<Vec<u64>> = vec![vec![]; NAMES.len()];
+    for _ in 0..rounds {
+        create_vec_rnd(893475343, &mut data[..]);
+        for algo in 0..NAMES.len() {
+            solo[algo].push(run(algo));
+        }
+    }
+    verify_sorted(&data[..]);
+
+    for _ in 0..rounds {
+        create_vec_rnd(893475343, &mut data[..]);
+        let barrier = Barrier::new(NAMES.len());
+        let barrier = &barrier;
+        let guards: Vec<_> = (0..NAMES.len()).map(|algo| unsafe {
+            thread_scoped::scoped(move || {
+                barrier.wait();
+                run(algo)
+            })
+        }).collect();
+        for (algo, guard) in guards.into_iter().enumerate() {
+            mixed[algo].push(guard.join());
+        }
+        verify_sorted(&data[..]);
+    }
+
+    NAMES.iter().enumerate().map(|(algo, &name)| {
+        Interference {
+            name: name,
+            solo: stats::median(&solo[algo][..]),
+            mixed: stats::median(&mixed[algo][..]),
+        }
+    }).collect()
+}
+
+fn mixed_fib_task(arg: MixedArg) -> TaskResult<MixedArg, usize> {
+    match arg {
+        MixedArg::Fib(n) => match fib_task(n) {
+            TaskResult::Done(v) => TaskResult::Done(v),
+            TaskResult::Fork(args, v) => TaskResult::Fork(args.into_iter().map(MixedArg::Fib).collect(), v),
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn mixed_sum_task(arg: MixedArg) -> TaskResult<MixedArg, usize> {
+    match arg {
+        MixedArg::Sum(t) => match sum_tree_task(t) {
+            TaskResult::Done(v) => TaskResult::Done(v),
+            TaskResult::Fork(args, v) => TaskResult::Fork(args.into_iter().map(MixedArg::Sum).collect(), v),
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn mixed_sort_task(arg: MixedArg) -> TaskResult<MixedArg, usize> {
+    match arg {
+        MixedArg::Sort(d) => match quicksort_task(d) {
+            TaskResult::Done(()) => TaskResult::Done(0),
+            TaskResult::Fork(args, _) => TaskResult::Fork(args.into_iter().map(MixedArg::Sort).collect(), None),
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn mixed_sort_join(_: &[usize]) -> usize {
+    0
+}
@@ -0,0 +1,158 @@
+use forkjoin::{TaskResult,ForkPool,AlgoStyle,ReduceStyle,Algorithm};
+use time;
+
+use std::mem;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use fib::{FIB, fib, fib_task};
+use sumtree::{Tree, sum_tree_join};
+use throughput::Shared;
+
+/// Outer tree nodes and, for the flat and same pool variants, the fib tasks in its leaves.
+pub enum NestedArg<'a> {
+    Tree(&'a Tree, Leaf),
+    Fib(usize),
+}
+
+/// What a leaf of the outer tree does.
+#[derive(Clone, Copy)]
+pub enum Leaf {
+    /// Fork fib(n) as subtasks of the same job
+    Fork(usize),
+    /// Schedule a job through the given function and block until it's done
+    Block(&'static (Fn() -> usize + Sync)),
+}
+
+/// Sequential reference. Every internal node contributes its value, every leaf its value plus fib(n).
+pub fn nested_seq(t: &Tree, n: usize) -> usize {
+    if t.children().is_empty() {
+        t.value() + fib(n)
+    } else {
+        t.value() + t.children().iter().fold(0, |acc, c| acc + nested_seq(c, n))
+    }
+}
+
+/// Flat parallelism, the fib tasks are forked as part of the tree job.
+pub fn flat_once(threads: usize, tree: &Tree, fib_n: usize) -> (usize, u64) {
+    let forkpool = ForkPool::with_threads(threads);
+    let nestedpool = forkpool.init_algorithm(nested_algorithm());
+
+    let start = time::precise_time_ns();
+    let job = nestedpool.schedule(NestedArg::Tree(tree, Leaf::Fork(fib_n)));
+    let result = job.recv().unwrap();
+    (result, time::precise_time_ns() - start)
+}
+
+/// Every leaf schedules fib on a second pool with `inner_threads` threads and blocks
+/// its worker until that job is done.
+pub fn separate_pools_once(threads: usize, inner_threads: usize, tree: &Tree, fib_n: usize) -> (usize, u64) {
+    let inner_forkpool = ForkPool::with_threads(inner_threads);
+    let fibpool = Shared(inner_forkpool.init_algorithm(FIB));
+    let lock = Mutex::new(());
+    let inner = || {
+        let job = {
+            let _guard = lock.lock().unwrap();
+            fibpool.0.schedule(fib_n)
+        };
+        job.recv().unwrap()
+    };
+    // The outer job is done before `inner` goes out of scope
+    let inner: &'static (Fn() -> usize + Sync) = unsafe { mem::transmute(&inner as &(Fn() -> usize + Sync)) };
+
+    let forkpool = ForkPool::with_threads(threads);
+    let nestedpool = forkpool.init_algorithm(nested_algorithm());
+
+    let start = time::precise_time_ns();
+    let job = nestedpool.schedule(NestedArg::Tree(tree, Leaf::Block(inner)));
+    let result = job.recv().unwrap();
+    (result, time::precise_time_ns() - start)
+}
+
+/// Every leaf schedules fib on the pool it is itself running on and blocks its worker
+/// until that job is done. Deadlocks if all workers end up blocked in leaves, so it is
+/// run on a separate thread and given up on after `timeout_ns` per sample.
+/// Returns None on timeout, the stuck pool is leaked.
+pub fn same_pool_watchdog(threads: usize, gen: fn(usize) -> Tree, depth: usize, fib_n: usize, samples: usize, timeout_ns: u64) -> Option<Vec<(usize, u64)>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let tree = gen(depth);
+        for _ in 0..samples {
+            if tx.send(same_pool_once(threads, &tree, fib_n)).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut results = vec![];
+    let mut last = time::precise_time_ns();
+    while results.len() < samples {
+        match rx.try_recv() {
+            Ok(result) => {
+                results.push(result);
+                last = time::precise_time_ns();
+            },
+            Err(mpsc::TryRecvError::Empty) => {
+                if time::precise_time_ns() - last > timeout_ns {
+                    return None;
+                }
+                thread::sleep(Duration::from_millis(10));
+            },
+            Err(mpsc::TryRecvError::Disconnected) => return None,
+        }
+    }
+    Some(results)
+}
+
+fn same_pool_once(threads: usize, tree: &Tree, fib_n: usize) -> (usize, u64) {
+    let forkpool = ForkPool::with_threads(threads);
+    let nestedpool = Shared(forkpool.init_algorithm(nested_algorithm()));
+    let lock = Mutex::new(());
+    let inner = || {
+        let job = {
+            let _guard = lock.lock().unwrap();
+            nestedpool.0.schedule(NestedArg::Fib(fib_n))
+        };
+        job.recv().unwrap()
+    };
+    // The outer job is done before `inner` goes out of scope
+    let inner: &'static (Fn() -> usize + Sync) = unsafe { mem::transmute(&inner as &(Fn() -> usize + Sync)) };
+
+    let start = time::precise_time_ns();
+    let job = {
+        let _guard = lock.lock().unwrap();
+        nestedpool.0.schedule(NestedArg::Tree(tree, Leaf::Block(inner)))
+    };
+    let result = job.recv().unwrap();
+    (result, time::precise_time_ns() - start)
+}
+
+fn nested_algorithm<'a>() -> Algorithm<NestedArg<'a>, usize> {
+    Algorithm {
+        fun: nested_task,
+        style: AlgoStyle::Reduce(ReduceStyle::Arg(sum_tree_join)),
+    }
+}
+
+fn nested_task(arg: NestedArg) -> TaskResult<NestedArg, usize> {
+    match arg {
+        NestedArg::Tree(t, leaf) => {
+            if !t.children().is_empty() {
+                let fork_args: Vec<NestedArg> = t.children().iter().map(|c| NestedArg::Tree(c, leaf)).collect();
+                TaskResult::Fork(fork_args, Some(t.value()))
+            } else {
+                match leaf {
+                    Leaf::Fork(n) => TaskResult::Fork(vec![NestedArg::Fib(n)], Some(t.value())),
+                    Leaf::Block(inner) => TaskResult::Done(t.value() + inner()),
+                }
+            }
+        },
+        NestedArg::Fib(n) => match fib_task(n) {
+            TaskResult::Done(v) => TaskResult::Done(v),
+            // The reduce style is shared with the tree nodes, so joins always need a value
+            TaskResult::Fork(args, v) => TaskResult::Fork(args.into_iter().map(NestedArg::Fib).collect(), Some(v.unwrap_or(0))),
+        },
+    }
+}
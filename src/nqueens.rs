@@ -2,6 +2,9 @@ use criterion::Bencher;
 use forkjoin::{ForkPool,TaskResult,AlgoStyle,ReduceStyle,Algorithm};
 use test;
 
+use backend::Join;
+use spawnpool::{PoolMode, bench_pool};
+
 pub fn seq_nqueens_reduce(b: &mut Bencher, &i: &usize) {
     b.iter(|| {
         let empty = vec![];
@@ -16,13 +19,10 @@ pub fn seq_nqueens_search(b: &mut Bencher, &i: &usize) {
     });
 }
 
-pub fn par_nqueens_reduce(b: &mut Bencher, threads: usize, &i: &usize) {
-    let forkpool = ForkPool::with_threads(threads);
-    let queenpool = forkpool.init_algorithm(NQUEENS_REDUCE);
-
+pub fn par_nqueens_reduce(b: &mut Bencher, threads: usize, mode: PoolMode, &i: &usize) {
     let expected_result = nqueens_reduce(&vec![][..], i);
 
-    b.iter_with_setup_and_verify(|| {}, |()| {
+    bench_pool(b, threads, mode, || NQUEENS_REDUCE, || {}, |queenpool, ()| {
         let empty = vec![];
         let job = queenpool.schedule(test::black_box((empty, i)));
         job.recv().unwrap()
@@ -31,13 +31,10 @@ pub fn par_nqueens_reduce(b: &mut Bencher, threads: usize, &i: &usize) {
     });
 }
 
-pub fn par_nqueens_search(b: &mut Bencher, threads: usize, &i: &usize) {
-    let forkpool = ForkPool::with_threads(threads);
-    let queenpool = forkpool.init_algorithm(NQUEENS_SEARCH);
-
+pub fn par_nqueens_search(b: &mut Bencher, threads: usize, mode: PoolMode, &i: &usize) {
     let expected_result = nqueens_reduce(&vec![][..], i);
 
-    b.iter_with_setup_and_verify(|| {}, |()| {
+    bench_pool(b, threads, mode, || NQUEENS_SEARCH, || {}, |queenpool, ()| {
         let empty = vec![];
         let job = queenpool.schedule(test::black_box((empty, i)));
         let mut solutions = vec![];
@@ -50,22 +47,29 @@ pub fn par_nqueens_search(b: &mut Bencher, threads: usize, &i: &usize) {
     });
 }
 
-pub fn par_nqueens_search_first(b: &mut Bencher, threads: usize, &i: &usize) {
-    let forkpool = ForkPool::with_threads(threads);
-    let queenpool = forkpool.init_algorithm(NQUEENS_SEARCH);
-
-    b.iter_with_setup_and_verify(|| {}, |()| {
+pub fn par_nqueens_search_first(b: &mut Bencher, threads: usize, mode: PoolMode, &i: &usize) {
+    // The job is dropped outside the timing. A fresh pool still tears down the rest of the search when it is dropped
+    bench_pool(b, threads, mode, || NQUEENS_SEARCH, || {}, |queenpool, ()| {
         let empty = vec![];
         let job = queenpool.schedule(test::black_box((empty, i)));
         let solution: Board = job.recv().unwrap();
         (solution, job)
     }, |(solution, job)| {
         assert!(ok(&solution[..]));
-        //queenpool.discard_all();
         drop(job);
     });
 }
 
+pub fn backend_nqueens_reduce<J: Join>(b: &mut Bencher, join: &J, &i: &usize) {
+    let expected_result = nqueens_reduce(&vec![][..], i);
+
+    b.iter_with_setup_and_verify(|| {}, |()| {
+        nqueens_backend(join, 0, test::black_box(vec![]), test::black_box(i))
+    }, |solutions| {
+        assert_eq!(expected_result.len(), solutions.len());
+    });
+}
+
 pub fn par_nqueens_reduce_once(threads: usize, i: usize) {
     let forkpool = ForkPool::with_threads(threads);
     let queenpool = forkpool.init_algorithm(NQUEENS_REDUCE);
@@ -75,12 +79,12 @@ pub fn par_nqueens_reduce_once(threads: usize, i: usize) {
     drop(test::black_box(job.recv().unwrap()));
 }
 
-const NQUEENS_SEARCH: Algorithm<(Board,usize), Board> = Algorithm {
+pub const NQUEENS_SEARCH: Algorithm<(Board,usize), Board> = Algorithm {
     fun: nqueens_task_search,
     style: AlgoStyle::Search,
 };
 
-const NQUEENS_REDUCE: Algorithm<(Board,usize), Solutions> = Algorithm {
+pub const NQUEENS_REDUCE: Algorithm<(Board,usize), Solutions> = Algorithm {
     fun: nqueens_task_reduce,
     style: AlgoStyle::Reduce(ReduceStyle::NoArg(nqueens_join)),
 };
@@ -123,6 +127,23 @@ fn nqueens_task_reduce((q, n): (Board, usize)) -> TaskResult<(Board,usize), Solu
     }
 }
 
+fn nqueens_backend<J: Join>(join: &J, depth: usize, q: Board, n: usize) -> Solutions {
+    if q.len() == n {
+        return vec![q];
+    }
+    let mut boards: Vec<Board> = vec![];
+    for i in 0..n {
+        let mut q2 = q.clone();
+        q2.push(i);
+
+        if ok(&q2[..]) {
+            boards.push(q2);
+        }
+    }
+    let tasks = boards.into_iter().map(|q2| move || nqueens_backend(join, depth + 1, q2, n)).collect();
+    nqueens_join(&join.join_all(depth, tasks)[..])
+}
+
 fn nqueens_join(values: &[Solutions]) -> Solutions {
     let mut all_solutions: Solutions = vec![];
     for solutions in values {
@@ -150,7 +171,7 @@ fn nqueens_search(q: &[Queen], n: usize) -> Option<Board> {
     return None;
 }
 
-fn nqueens_reduce(q: &[Queen], n: usize) -> Solutions {
+pub fn nqueens_reduce(q: &[Queen], n: usize) -> Solutions {
     if q.len() == n {
         return vec![q.to_vec()];
     }
@@ -0,0 +1,273 @@
+//! Placement of the sort benchmarks' input across NUMA nodes. Linux places a page on
+//! the node of the thread that first writes it, so the input is mapped fresh and populated
+//! either by the driver thread, by the workers of a pool, or under an interleaving memory
+//! policy. On machines with a single node every policy is a plain mapping.
+
+use forkjoin::{TaskResult, ForkPool, AlgoStyle, ReduceStyle, Algorithm};
+use std::cmp;
+use std::ops::{Deref, DerefMut};
+use std::slice;
+
+use cpus;
+use pin;
+use self::policy::{map, unmap, bind_interleave, page_nodes};
+
+/// Elements written by one task of the first-touch pass, 32 pages of usizes
+const TOUCH_CHUNK: usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Policy {
+    /// Written by the driver thread, placing everything on its node
+    Local,
+    /// Written by the workers of a pool with the benchmark's thread count, pinned like the
+    /// benchmark's own workers
+    FirstTouch,
+    /// Spread round robin over all nodes with `mbind(MPOL_INTERLEAVE)`
+    Interleave,
+}
+
+impl Policy {
+    pub fn parse(name: &str) -> Policy {
+        match name {
+            "local" => Policy::Local,
+            "first-touch" => Policy::FirstTouch,
+            "interleave" => Policy::Interleave,
+            other => panic!("Invalid NUMA policy: {}", other),
+        }
+    }
+
+    /// Suffix for implementation names. Empty for local, the default policy.
+    pub fn suffix(&self) -> &'static str {
+        match *self {
+            Policy::Local => "",
+            Policy::FirstTouch => "_firsttouch",
+            Policy::Interleave => "_interleave",
+        }
+    }
+}
+
+/// The online NUMA nodes, empty if the kernel does not report any.
+pub fn nodes() -> Vec<usize> {
+    cpus::read_file("/sys/devices/system/node/online")
+        .map(|online| cpus::parse_cpu_list(online.trim()))
+        .unwrap_or(vec![])
+}
+
+/// The input of a sort benchmark in pages mapped for it alone. Pages the allocator
+/// reuses may have been written by an earlier benchmark, which placed them for good.
+pub struct Buffer {
+    ptr: *mut usize,
+    len: usize,
+}
+
+impl Buffer {
+    /// `len` zeroes in untouched pages.
+    fn map(len: usize) -> Buffer {
+        Buffer {
+            ptr: map(len),
+            len: len,
+        }
+    }
+}
+
+impl Deref for Buffer {
+    type Target = [usize];
+
+    fn deref(&self) -> &[usize] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut [usize] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unmap(self.ptr, self.len);
+    }
+}
+
+/// A buffer of `size` zeroes placed according to `policy`, with a warning if the pages
+/// did not end up where the policy puts them. `worker_cpus` are the CPUs of the benchmark's workers.
+pub fn create_buffer(policy: Policy, size: usize, threads: usize, worker_cpus: &[usize]) -> Buffer {
+    let mut data = Buffer::map(size);
+    let nodes = nodes();
+    if nodes.len() <= 1 {
+        return data;
+    }
+    match policy {
+        Policy::Local => touch(&mut data),
+        Policy::FirstTouch => {
+            let (forkpool, _) = pin::pinned(worker_cpus, threads, || ForkPool::with_threads(threads));
+            let touchpool = forkpool.init_algorithm(Algorithm {
+                fun: touch_task,
+                style: AlgoStyle::Reduce(ReduceStyle::NoArg(touch_join)),
+            });
+            let job = touchpool.schedule(&mut data[..]);
+            job.recv().unwrap();
+        },
+        Policy::Interleave => {
+            if !bind_interleave(&data, &nodes) {
+                println!("Warning: mbind(MPOL_INTERLEAVE) failed, placing input locally");
+            }
+            touch(&mut data);
+        },
+    }
+    check_placement(&data, policy, nodes.len());
+    data
+}
+
+fn touch(d: &mut [usize]) {
+    for x in d.iter_mut() {
+        *x = 0;
+    }
+}
+
+/// Warns if local input spans several nodes or interleaved input misses some. Where
+/// first touch puts the pages depends on where the workers ran, so that is not checked.
+fn check_placement(data: &[usize], policy: Policy, nodes: usize) {
+    let mut used = match page_nodes(data) {
+        Some(used) => used,
+        None => {
+            println!("Warning: get_mempolicy(MPOL_F_ADDR) failed, could not check the input placement");
+            return;
+        },
+    };
+    let pages = used.len();
+    used.sort();
+    used.dedup();
+    match policy {
+        Policy::Local if used.len() > 1 => {
+            println!("Warning: local input spans NUMA nodes {:?}", used);
+        },
+        Policy::Interleave if used.len() < cmp::min(nodes, pages) => {
+            println!("Warning: interleaved input is only on NUMA nodes {:?} of {}", used, nodes);
+        },
+        _ => (),
+    }
+}
+
+fn touch_task(d: &mut [usize]) -> TaskResult<&mut [usize], ()> {
+    if d.len() <= TOUCH_CHUNK {
+        for x in d.iter_mut() {
+            *x = 0;
+        }
+        TaskResult::Done(())
+    } else {
+        let mid = d.len() / 2;
+        let (low, high) = d.split_at_mut(mid);
+        TaskResult::Fork(vec![low, high], None)
+    }
+}
+
+fn touch_join(_: &[()]) -> () {}
+
+#[cfg(target_os = "linux")]
+mod policy {
+    use libc::{self, c_int, c_long, c_ulong, c_void};
+    use std::{cmp, mem, ptr};
+
+    #[cfg(target_arch = "x86_64")]
+    const SYS_MBIND: c_long = 237;
+    #[cfg(target_arch = "x86_64")]
+    const SYS_GET_MEMPOLICY: c_long = 239;
+    #[cfg(target_arch = "x86")]
+    const SYS_MBIND: c_long = 274;
+    #[cfg(target_arch = "x86")]
+    const SYS_GET_MEMPOLICY: c_long = 275;
+    #[cfg(target_arch = "aarch64")]
+    const SYS_MBIND: c_long = 235;
+    #[cfg(target_arch = "aarch64")]
+    const SYS_GET_MEMPOLICY: c_long = 236;
+    #[cfg(target_arch = "arm")]
+    const SYS_MBIND: c_long = 319;
+    #[cfg(target_arch = "arm")]
+    const SYS_GET_MEMPOLICY: c_long = 320;
+
+    const MPOL_INTERLEAVE: c_long = 3;
+    const MPOL_F_NODE: c_ulong = 1;
+    const MPOL_F_ADDR: c_ulong = 2;
+
+    fn bytes(len: usize) -> usize {
+        // mmap rejects empty mappings
+        cmp::max(len * mem::size_of::<usize>(), 1)
+    }
+
+    /// `len` zeroed usizes in a private anonymous mapping.
+    pub fn map(len: usize) -> *mut usize {
+        let p = unsafe {
+            libc::mmap(ptr::null_mut(), bytes(len), libc::PROT_READ | libc::PROT_WRITE, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0)
+        };
+        if p == libc::MAP_FAILED {
+            panic!("Could not map {} bytes for the sort input", bytes(len));
+        }
+        p as *mut usize
+    }
+
+    pub fn unmap(p: *mut usize, len: usize) {
+        unsafe { libc::munmap(p as *mut c_void, bytes(len)) };
+    }
+
+    /// Interleaves the pages of `data` over `nodes` once they are touched.
+    pub fn bind_interleave(data: &[usize], nodes: &[usize]) -> bool {
+        let bits = 8 * mem::size_of::<c_ulong>();
+        let mut mask = [0 as c_ulong; 16];
+        let max_nodes = bits * mask.len();
+        for &node in nodes.iter().filter(|&&n| n < max_nodes) {
+            mask[node / bits] |= 1 << (node % bits);
+        }
+        // The kernel reads maxnode - 1 bits
+        let maxnode = (max_nodes + 1) as c_ulong;
+        unsafe {
+            libc::syscall(SYS_MBIND, data.as_ptr(), bytes(data.len()) as c_ulong, MPOL_INTERLEAVE, mask.as_ptr(), maxnode, 0 as c_ulong) == 0
+        }
+    }
+
+    /// The node of every page of `data`, which must have been touched.
+    pub fn page_nodes(data: &[usize]) -> Option<Vec<usize>> {
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let start = data.as_ptr() as usize;
+        let end = start + data.len() * mem::size_of::<usize>();
+        let mut nodes = vec![];
+        let mut addr = start;
+        while addr < end {
+            let mut node: c_int = -1;
+            let ok = unsafe {
+                libc::syscall(SYS_GET_MEMPOLICY, &mut node as *mut c_int, ptr::null_mut::<c_ulong>(), 0 as c_ulong, addr, MPOL_F_NODE | MPOL_F_ADDR) == 0
+            };
+            if !ok || node < 0 {
+                return None;
+            }
+            nodes.push(node as usize);
+            addr += page;
+        }
+        Some(nodes)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod policy {
+    use std::mem;
+
+    pub fn map(len: usize) -> *mut usize {
+        let mut data: Vec<usize> = vec![0; len];
+        let p = data.as_mut_ptr();
+        mem::forget(data);
+        p
+    }
+
+    pub fn unmap(p: *mut usize, len: usize) {
+        drop(unsafe { Vec::from_raw_parts(p, len, len) });
+    }
+
+    pub fn bind_interleave(_data: &[usize], _nodes: &[usize]) -> bool {
+        false
+    }
+
+    pub fn page_nodes(_data: &[usize]) -> Option<Vec<usize>> {
+        None
+    }
+}
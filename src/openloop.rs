@@ -0,0 +1,171 @@
+use forkjoin::ForkPool;
+use thread_scoped;
+use time;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use fib::{FIB, fib};
+
+/// How the time between two job submissions is distributed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arrival {
+    /// Exponentially distributed, jobs arrive as a Poisson process
+    Poisson,
+    /// Exactly 1/rate between every job
+    Constant,
+}
+
+impl Arrival {
+    pub fn parse(name: &str) -> Arrival {
+        match name {
+            "poisson" => Arrival::Poisson,
+            "constant" => Arrival::Constant,
+            other => panic!("Invalid arrival process: {}", other),
+        }
+    }
+}
+
+pub struct OpenLoop {
+    /// Latencies measured from when each job was supposed to be submitted, in ns
+    pub corrected: Vec<u64>,
+    /// Latencies measured from when each job was actually submitted, in ns
+    pub uncorrected: Vec<u64>,
+    /// Number of jobs submitted more than 1 ms after their intended time
+    pub late: usize,
+}
+
+/// Intended submission time of every job, in ns after the start, for `rate` jobs/s.
+pub fn arrival_times(arrival: Arrival, rate: f64, jobs: usize, seed: u64) -> Vec<u64> {
+    let mean_ns = 1e9 / rate;
+    let mut rng = XorShift(if seed == 0 { 1 } else { seed });
+    let mut t = 0.0;
+    (0..jobs).map(|_| {
+        let at = t as u64;
+        t += match arrival {
+            Arrival::Poisson => -(1.0 - rng.next_f64()).ln() * mean_ns,
+            Arrival::Constant => mean_ns,
+        };
+        at
+    }).collect()
+}
+
+/// Submits `jobs` fib(n) jobs to one pool at `rate` jobs/s. A single submitter schedules
+/// every job at its intended time without waiting for any of them, so the rate holds no
+/// matter how fast the jobs complete. If the submitter itself falls behind a job is sent
+/// late, but its corrected latency still counts from the intended time (coordinated
+/// omission). The results are waited for by `receivers` separate threads.
+pub fn fib_open_loop(threads: usize, receivers: usize, arrival: Arrival, rate: f64, jobs: usize, n: usize) -> OpenLoop {
+    let forkpool = ForkPool::with_threads(threads);
+    let fibpool = forkpool.init_algorithm(FIB);
+    let expected = fib(n);
+
+    let arrivals = arrival_times(arrival, rate, jobs, 893475343);
+    run_open_loop(receivers, &arrivals[..], || fibpool.schedule(n), |job| {
+        assert_eq!(expected, job.recv().unwrap());
+    })
+}
+
+/// Calls `schedule` on the calling thread at every arrival time, in ns after the start,
+/// and hands each job together with its intended and actual submission time to one of
+/// `receivers` threads, round robin, which waits for it with `wait` and records its
+/// latency. A receiver waits for its jobs in submission order, so a job finishing while
+/// its receiver still waits for an earlier one is recorded late.
+fn run_open_loop<S, W, J>(receivers: usize, arrivals: &[u64], mut schedule: S, wait: W) -> OpenLoop where
+    S: FnMut() -> J,
+    W: Fn(J) + Sync,
+    J: Send,
+{
+    assert!(receivers > 0, "Need at least one receiver");
+    let wait = &wait;
+
+    let mut senders = Vec::with_capacity(receivers);
+    let mut guards = Vec::with_capacity(receivers);
+    for _ in 0..receivers {
+        let (sender, jobs) = mpsc::channel::<(u64, u64, J)>();
+        senders.push(sender);
+        guards.push(unsafe {
+            thread_scoped::scoped(move || {
+                let mut results = vec![];
+                for (intended, sent, job) in jobs {
+                    wait(job);
+                    let done = time::precise_time_ns();
+                    results.push((done - intended, done - sent, sent - intended));
+                }
+                results
+            })
+        });
+    }
+
+    let start = time::precise_time_ns();
+    for (i, &at) in arrivals.iter().enumerate() {
+        let intended = start + at;
+        sleep_until(intended);
+
+        let sent = time::precise_time_ns();
+        let job = schedule();
+        senders[i % receivers].send((intended, sent, job)).unwrap();
+    }
+    // Lets the receivers finish once they got all their jobs
+    drop(senders);
+
+    let mut result = OpenLoop {
+        corrected: Vec::with_capacity(arrivals.len()),
+        uncorrected: Vec::with_capacity(arrivals.len()),
+        late: 0,
+    };
+    for guard in guards {
+        for (corrected, uncorrected, lateness) in guard.join() {
+            result.corrected.push(corrected);
+            result.uncorrected.push(uncorrected);
+            if lateness > 1_000_000 {
+                result.late += 1;
+            }
+        }
+    }
+    result
+}
+
+/// Sleeps most of the way and spins the last bit to hit `target` accurately.
+fn sleep_until(target: u64) {
+    loop {
+        let now = time::precise_time_ns();
+        if now >= target {
+            return;
+        }
+        let left = target - now;
+        if left > 200_000 {
+            thread::sleep(Duration::new(0, (left - 100_000) as u32));
+        } else {
+            thread::yield_now();
+        }
+    }
+}
+
+/// xorshift64*, good enough for inter-arrival times and reproducible between runs.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(2685821657736338717)
+    }
+
+    /// Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[test]
+fn test_arrival_times() {
+    let constant = arrival_times(Arrival::Constant, 1000.0, 4, 1);
+    assert_eq!(vec![0, 1_000_000, 2_000_000, 3_000_000], constant);
+
+    let poisson = arrival_times(Arrival::Poisson, 1000.0, 10001, 1);
+    let mean_ns = poisson[10000] as f64 / 10000.0;
+    assert!(mean_ns > 950_000.0 && mean_ns < 1_050_000.0);
+}
@@ -0,0 +1,198 @@
+//! Hardware and software event counters through `perf_event_open`. Only compiled in with
+//! the `perf-counters` feature on Linux, everywhere else `Counters::open` returns None.
+//!
+//! The counters follow the opening thread and every thread it creates afterwards, so
+//! they must be opened before the pool whose work they should count.
+
+pub const EVENTS: [&'static str; 6] = ["cycles", "instructions", "cache-misses", "branch-misses", "context-switches", "cpu-migrations"];
+
+/// Event values, None for events the kernel refused to count.
+pub type Counts = [Option<u64>; 6];
+
+#[cfg(all(feature = "perf-counters", target_os = "linux"))]
+pub use self::linux::Counters;
+
+#[cfg(not(all(feature = "perf-counters", target_os = "linux")))]
+pub struct Counters;
+
+#[cfg(not(all(feature = "perf-counters", target_os = "linux")))]
+impl Counters {
+    pub fn open() -> Option<Counters> {
+        None
+    }
+
+    pub fn read(&self) -> Counts {
+        [None; 6]
+    }
+
+    pub fn mode(&self) -> &'static str {
+        "none"
+    }
+}
+
+/// Per event average of `after - before` over all samples.
+pub fn average(deltas: &[Counts]) -> Vec<Option<f64>> {
+    (0..EVENTS.len()).map(|event| {
+        if deltas.is_empty() || deltas.iter().any(|d| d[event].is_none()) {
+            None
+        } else {
+            Some(deltas.iter().fold(0.0, |acc, d| acc + d[event].unwrap() as f64) / deltas.len() as f64)
+        }
+    }).collect()
+}
+
+pub fn delta(before: &Counts, after: &Counts) -> Counts {
+    let mut delta = [None; 6];
+    for event in 0..EVENTS.len() {
+        delta[event] = match (before[event], after[event]) {
+            (Some(b), Some(a)) => Some(a.saturating_sub(b)),
+            _ => None,
+        };
+    }
+    delta
+}
+
+#[cfg(all(feature = "perf-counters", target_os = "linux"))]
+mod linux {
+    use libc::{self, c_int, c_long, c_void};
+    use std::mem;
+
+    use super::{Counts, EVENTS};
+
+    #[cfg(target_arch = "x86_64")]
+    const SYS_PERF_EVENT_OPEN: c_long = 298;
+    #[cfg(target_arch = "x86")]
+    const SYS_PERF_EVENT_OPEN: c_long = 336;
+    #[cfg(target_arch = "aarch64")]
+    const SYS_PERF_EVENT_OPEN: c_long = 241;
+    #[cfg(target_arch = "arm")]
+    const SYS_PERF_EVENT_OPEN: c_long = 364;
+
+    const TYPE_HARDWARE: u32 = 0;
+    const TYPE_SOFTWARE: u32 = 1;
+
+    /// (type, config) of every entry in `EVENTS`
+    const CONFIGS: [(u32, u64); 6] = [
+        (TYPE_HARDWARE, 0), // PERF_COUNT_HW_CPU_CYCLES
+        (TYPE_HARDWARE, 1), // PERF_COUNT_HW_INSTRUCTIONS
+        (TYPE_HARDWARE, 3), // PERF_COUNT_HW_CACHE_MISSES
+        (TYPE_HARDWARE, 5), // PERF_COUNT_HW_BRANCH_MISSES
+        (TYPE_SOFTWARE, 3), // PERF_COUNT_SW_CONTEXT_SWITCHES
+        (TYPE_SOFTWARE, 4), // PERF_COUNT_SW_CPU_MIGRATIONS
+    ];
+
+    const FLAG_INHERIT: u64 = 1 << 1;
+    const FLAG_EXCLUDE_KERNEL: u64 = 1 << 5;
+    const FLAG_EXCLUDE_HV: u64 = 1 << 6;
+
+    /// `struct perf_event_attr` up to PERF_ATTR_SIZE_VER1.
+    #[repr(C)]
+    struct PerfEventAttr {
+        type_: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        wakeup_events: u32,
+        bp_type: u32,
+        config1: u64,
+        config2: u64,
+    }
+
+    pub struct Counters {
+        fds: Vec<Option<c_int>>,
+        user_only: bool,
+    }
+
+    impl Counters {
+        /// Opens every event for this thread and the threads it creates from now on.
+        /// Returns None if not a single event could be opened, e.g. because of
+        /// /proc/sys/kernel/perf_event_paranoid or missing PMU access in a VM.
+        ///
+        /// Counting kernel space needs more privileges. All events count user space only
+        /// if that opens more of them, so their values stay comparable with each other.
+        pub fn open() -> Option<Counters> {
+            let all = Counters::open_all(FLAG_INHERIT | FLAG_EXCLUDE_HV, false);
+            if all.opened() == EVENTS.len() {
+                return Some(all);
+            }
+            let user = Counters::open_all(FLAG_INHERIT | FLAG_EXCLUDE_HV | FLAG_EXCLUDE_KERNEL, true);
+            let counters = if user.opened() > all.opened() { user } else { all };
+            if counters.opened() == 0 {
+                None
+            } else {
+                Some(counters)
+            }
+        }
+
+        fn open_all(flags: u64, user_only: bool) -> Counters {
+            Counters {
+                fds: CONFIGS.iter().map(|&(type_, config)| open_event(type_, config, flags)).collect(),
+                user_only: user_only,
+            }
+        }
+
+        fn opened(&self) -> usize {
+            self.fds.iter().filter(|fd| fd.is_some()).count()
+        }
+
+        /// Which privilege levels every event counts.
+        pub fn mode(&self) -> &'static str {
+            if self.user_only { "user space only" } else { "user and kernel space" }
+        }
+
+        /// The current value of every event, summed over all counted threads.
+        pub fn read(&self) -> Counts {
+            let mut counts = [None; 6];
+            for event in 0..EVENTS.len() {
+                counts[event] = self.fds[event].and_then(|fd| {
+                    let mut value: u64 = 0;
+                    let n = unsafe { libc::read(fd, &mut value as *mut u64 as *mut c_void, mem::size_of::<u64>() as libc::size_t) };
+                    if n == mem::size_of::<u64>() as libc::ssize_t { Some(value) } else { None }
+                });
+            }
+            counts
+        }
+    }
+
+    impl Drop for Counters {
+        fn drop(&mut self) {
+            for fd in self.fds.iter().filter_map(|&fd| fd) {
+                unsafe { libc::close(fd) };
+            }
+        }
+    }
+
+    fn open_event(type_: u32, config: u64, flags: u64) -> Option<c_int> {
+        let attr = PerfEventAttr {
+            type_: type_,
+            size: mem::size_of::<PerfEventAttr>() as u32,
+            config: config,
+            sample_period: 0,
+            sample_type: 0,
+            read_format: 0,
+            flags: flags,
+            wakeup_events: 0,
+            bp_type: 0,
+            config1: 0,
+            config2: 0,
+        };
+        // This thread on any CPU, no group, no flags
+        let fd = unsafe { libc::syscall(SYS_PERF_EVENT_OPEN, &attr as *const PerfEventAttr, 0 as c_int, -1 as c_int, -1 as c_int, 0 as libc::c_ulong) };
+        if fd < 0 { None } else { Some(fd as c_int) }
+    }
+}
+
+#[test]
+fn test_average() {
+    let deltas = [
+        [Some(10), Some(20), None, Some(1), Some(0), Some(0)],
+        [Some(30), Some(40), Some(5), Some(3), Some(0), Some(0)],
+    ];
+    let averages = average(&deltas[..]);
+    assert_eq!(Some(20.0), averages[0]);
+    assert_eq!(None, averages[2]);
+    assert_eq!([Some(5), None, None, None, None, None], delta(&[Some(5), None, None, None, None, None], &[Some(10), Some(3), None, None, None, None]));
+}
@@ -0,0 +1,212 @@
+//! Placement of the driver and worker threads on CPUs. Forkjoin creates its workers
+//! itself, so they are found as the threads that appeared in /proc/self/task while a
+//! pool was created, and pinned one CPU each in creation order. The threads of the other
+//! backends are only kept on the workers' CPUs, with `allow_current` when they start.
+
+use std::fs;
+
+use cpus;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pin {
+    /// Leave placement to the scheduler
+    None,
+    /// Fill the SMT siblings of a core before moving to the next core
+    Compact,
+    /// One thread per physical core, spread over the packages, before using siblings
+    Scatter,
+    /// These CPUs, in order
+    List(Vec<usize>),
+}
+
+impl Pin {
+    pub fn parse(s: &str) -> Pin {
+        match s {
+            "none" => Pin::None,
+            "compact" => Pin::Compact,
+            "scatter" => Pin::Scatter,
+            _ if s.starts_with("list:") => Pin::List(cpus::parse_cpu_list(&s["list:".len()..])),
+            _ => panic!("Invalid pinning strategy: {}", s),
+        }
+    }
+
+    /// The CPU of every thread, `split` into the driver's and the workers'. Empty for `None`.
+    pub fn cpus(&self) -> Vec<usize> {
+        match *self {
+            Pin::None => vec![],
+            Pin::Compact => compact(cpus::topology()),
+            Pin::Scatter => scatter(cpus::topology()),
+            Pin::List(ref cpus) => cpus.clone(),
+        }
+    }
+}
+
+/// CPUs ordered by package, core and CPU number.
+fn compact(mut topology: Vec<(usize, usize, usize)>) -> Vec<usize> {
+    topology.sort_by_key(|&(cpu, package, core)| (package, core, cpu));
+    topology.iter().map(|&(cpu, _, _)| cpu).collect()
+}
+
+/// CPUs ordered by their rank among the siblings of their core, then by the rank of
+/// their core within its package, then by package.
+fn scatter(topology: Vec<(usize, usize, usize)>) -> Vec<usize> {
+    let mut ranked: Vec<(usize, usize, usize, usize)> = topology.iter().map(|&(cpu, package, core)| {
+        let sibling = topology.iter().filter(|&&(c, p, k)| p == package && k == core && c < cpu).count();
+        let mut cores: Vec<usize> = topology.iter().filter(|&&(_, p, _)| p == package).map(|&(_, _, k)| k).collect();
+        cores.sort();
+        cores.dedup();
+        let core_rank = cores.iter().position(|&k| k == core).unwrap();
+        (sibling, core_rank, package, cpu)
+    }).collect();
+    ranked.sort();
+    ranked.iter().map(|&(_, _, _, cpu)| cpu).collect()
+}
+
+/// Splits `cpus` into the CPU of the driver and the CPUs of the workers. The driver
+/// gets a CPU of its own, unless there is only one to share with the workers.
+pub fn split(cpus: &[usize]) -> (Option<usize>, &[usize]) {
+    match cpus.len() {
+        0 => (None, cpus),
+        1 => (Some(cpus[0]), cpus),
+        _ => (Some(cpus[0]), &cpus[1..]),
+    }
+}
+
+/// Calls `create`, which starts `threads` worker threads, and pins the threads that
+/// appeared meanwhile to `cpus`. Returns what `create` returned and the (thread id, CPU)
+/// mapping, which is empty if `cpus` is.
+pub fn pinned<T, F: FnOnce() -> T>(cpus: &[usize], threads: usize, create: F) -> (T, Vec<(i32, usize)>) {
+    if cpus.is_empty() {
+        return (create(), vec![]);
+    }
+    let before = thread_ids();
+    let created = create();
+    let pinned = pin_new_threads(&before, cpus);
+    if pinned.len() != threads {
+        println!("Warning: pinned {} new threads, expected {} workers", pinned.len(), threads);
+    }
+    (created, pinned)
+}
+
+/// Ids of all threads of this process.
+pub fn thread_ids() -> Vec<i32> {
+    let mut tids: Vec<i32> = match fs::read_dir("/proc/self/task") {
+        Ok(entries) => entries.filter_map(|e| e.ok())
+            .filter_map(|e| e.file_name().to_str().and_then(|name| name.parse().ok()))
+            .collect(),
+        Err(_) => vec![],
+    };
+    tids.sort();
+    tids
+}
+
+/// Pins the threads not in `before` to `cpus`, in order of their thread id, wrapping
+/// around if there are more threads than CPUs. Returns the (thread id, CPU) mapping.
+pub fn pin_new_threads(before: &[i32], cpus: &[usize]) -> Vec<(i32, usize)> {
+    if cpus.is_empty() {
+        return vec![];
+    }
+    let new: Vec<i32> = thread_ids().into_iter().filter(|tid| !before.contains(tid)).collect();
+    new.iter().enumerate().map(|(i, &tid)| {
+        let cpu = cpus[i % cpus.len()];
+        if !set_affinity(tid, &[cpu]) {
+            println!("Warning: could not pin thread {} to CPU {}", tid, cpu);
+        }
+        (tid, cpu)
+    }).collect()
+}
+
+/// Id of the calling thread, as listed in /proc/self/task.
+#[cfg(target_os = "linux")]
+pub fn current_thread_id() -> i32 {
+    use libc::{self, c_long};
+
+    #[cfg(target_arch = "x86_64")]
+    const SYS_GETTID: c_long = 186;
+    #[cfg(target_arch = "x86")]
+    const SYS_GETTID: c_long = 224;
+    #[cfg(target_arch = "aarch64")]
+    const SYS_GETTID: c_long = 178;
+    #[cfg(target_arch = "arm")]
+    const SYS_GETTID: c_long = 224;
+
+    unsafe { libc::syscall(SYS_GETTID) as i32 }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn current_thread_id() -> i32 {
+    0
+}
+
+/// Pins the calling thread to `cpu`.
+pub fn pin_current(cpu: usize) -> bool {
+    set_affinity(0, &[cpu])
+}
+
+/// Lets the calling thread run on any of `cpus`. Does nothing if `cpus` is empty.
+pub fn allow_current(cpus: &[usize]) -> bool {
+    cpus.is_empty() || set_affinity(0, cpus)
+}
+
+/// The CPUs the calling thread may run on, empty if they can't be read.
+#[cfg(target_os = "linux")]
+pub fn current_affinity() -> Vec<usize> {
+    use libc::{c_int, c_ulong, pid_t, size_t};
+    use std::mem;
+
+    extern {
+        fn sched_getaffinity(pid: pid_t, cpusetsize: size_t, mask: *mut c_ulong) -> c_int;
+    }
+
+    let bits = 8 * mem::size_of::<c_ulong>();
+    let words = 1024 / bits;
+    let mut mask = [0 as c_ulong; 32];
+    if unsafe { sched_getaffinity(0, (words * mem::size_of::<c_ulong>()) as size_t, mask.as_mut_ptr()) } != 0 {
+        return vec![];
+    }
+    (0..1024).filter(|&cpu| mask[cpu / bits] & (1 << (cpu % bits)) != 0).collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn current_affinity() -> Vec<usize> {
+    vec![]
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(tid: i32, cpus: &[usize]) -> bool {
+    use libc::{c_int, c_ulong, pid_t, size_t};
+    use std::mem;
+
+    extern {
+        fn sched_setaffinity(pid: pid_t, cpusetsize: size_t, mask: *const c_ulong) -> c_int;
+    }
+
+    // Room for 1024 CPUs, like glibc's cpu_set_t
+    let bits = 8 * mem::size_of::<c_ulong>();
+    let words = 1024 / bits;
+    let mut mask = [0 as c_ulong; 32];
+    for &cpu in cpus {
+        if cpu >= 1024 {
+            return false;
+        }
+        mask[cpu / bits] |= 1 << (cpu % bits);
+    }
+    unsafe { sched_setaffinity(tid as pid_t, (words * mem::size_of::<c_ulong>()) as size_t, mask.as_ptr()) == 0 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_affinity(_tid: i32, _cpus: &[usize]) -> bool {
+    false
+}
+
+#[test]
+fn test_placement() {
+    // Two packages with two cores of two siblings each
+    let topology = vec![(0, 0, 0), (1, 0, 1), (2, 1, 0), (3, 1, 1), (4, 0, 0), (5, 0, 1), (6, 1, 0), (7, 1, 1)];
+    assert_eq!(vec![0, 4, 1, 5, 2, 6, 3, 7], compact(topology.clone()));
+    assert_eq!(vec![0, 2, 1, 3, 4, 6, 5, 7], scatter(topology));
+    assert_eq!(Pin::List(vec![0, 2, 4]), Pin::parse("list:0,2,4"));
+    assert_eq!((Some(0), &[2, 4][..]), split(&[0, 2, 4]));
+    assert_eq!((Some(3), &[3][..]), split(&[3]));
+    assert_eq!((None, &[][..]), split(&[]));
+}
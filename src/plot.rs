@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use baseline::Row;
+use stats;
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 400.0;
+const MARGIN: f64 = 60.0;
+const COLORS: [&'static str; 6] = ["#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b"];
+
+/// Median time per thread count of one implementation variant, e.g. "" or "size1000".
+struct Series {
+    variant: String,
+    points: Vec<(usize, u64)>,
+}
+
+/// Writes runtime, speedup and efficiency plots of every workload in `rows` into `dir`,
+/// plus a heatmap of thread count against variant for workloads with more than one
+/// variant (the sumtree cutoff sweeps), and an index.html showing all of them.
+/// Implementations are expected to be named "T<threads>" or "T<threads>_<variant>", and
+/// the sequential implementation "seq", which the speedups are relative to if present.
+pub fn write_plots(dir: &Path, rows: &[Row]) -> io::Result<()> {
+    try!(fs::create_dir_all(dir));
+    let mut workloads: Vec<&str> = vec![];
+    let mut grouped: BTreeMap<&str, Vec<Series>> = BTreeMap::new();
+    let mut seq: BTreeMap<&str, u64> = BTreeMap::new();
+    for row in rows {
+        if row.implementation == "seq" {
+            seq.insert(&row.workload, stats::median(&row.samples[..]));
+            continue;
+        }
+        let (threads, variant) = match parse_implementation(&row.implementation) {
+            Some(parsed) => parsed,
+            None => continue,
+        };
+        if !grouped.contains_key(&row.workload[..]) {
+            workloads.push(&row.workload);
+        }
+        let series = grouped.entry(&row.workload[..]).or_insert(vec![]);
+        let median = stats::median(&row.samples[..]);
+        if let Some(s) = series.iter_mut().find(|s| s.variant == variant) {
+            s.points.push((threads, median));
+            continue;
+        }
+        series.push(Series {
+            variant: variant,
+            points: vec![(threads, median)],
+        });
+    }
+
+    let mut index = String::from("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>ForkJoin benchmarks</title></head><body>\n");
+    for workload in workloads {
+        let mut series = grouped.remove(workload).unwrap();
+        for s in series.iter_mut() {
+            s.points.sort();
+        }
+        index.push_str(&format!("<h2>{}</h2>\n", workload));
+
+        let seq_ns = seq.get(workload).cloned();
+        let mut plots = vec![
+            ("runtime", runtime_plot(workload, &series)),
+            ("speedup", speedup_plot(workload, &series, seq_ns, false)),
+            ("efficiency", speedup_plot(workload, &series, seq_ns, true)),
+        ];
+        if series.len() > 1 {
+            plots.push(("heatmap", heatmap(workload, &series)));
+        }
+        for (kind, svg) in plots {
+            let file_name = format!("{}_{}.svg", workload, kind);
+            let mut file = try!(File::create(dir.join(&file_name)));
+            try!(file.write_all(svg.as_bytes()));
+            index.push_str(&format!("<a href=\"{0}\"><img src=\"{0}\" alt=\"{1} {2}\"></a>\n", file_name, workload, kind));
+        }
+    }
+    index.push_str("</body></html>\n");
+    let mut file = try!(File::create(dir.join("index.html")));
+    file.write_all(index.as_bytes())
+}
+
+fn parse_implementation(implementation: &str) -> Option<(usize, String)> {
+    if !implementation.starts_with('T') {
+        return None;
+    }
+    let rest = &implementation[1..];
+    let (threads, variant) = match rest.find('_') {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, ""),
+    };
+    threads.parse().ok().map(|t| (t, variant.to_string()))
+}
+
+fn label(variant: &str) -> &str {
+    if variant.is_empty() { "ForkJoin" } else { variant }
+}
+
+fn runtime_plot(workload: &str, series: &[Series]) -> String {
+    let lines: Vec<(&str, Vec<(f64, f64)>)> = series.iter().map(|s| {
+        (label(&s.variant), s.points.iter().map(|&(t, ns)| (t as f64, ns as f64 / 1e6)).collect())
+    }).collect();
+    line_chart(&format!("{} runtime", workload), "threads", "median time (ms)", &lines, None)
+}
+
+/// Speedup over the sequential implementation taking `seq_ns`, or without it relative
+/// speedup over the lowest thread count measured, scaled as if that count had perfect
+/// speedup. Efficiency (speedup / threads) instead if `efficiency` is set.
+fn speedup_plot(workload: &str, series: &[Series], seq_ns: Option<u64>, efficiency: bool) -> String {
+    let lines: Vec<(&str, Vec<(f64, f64)>)> = series.iter().map(|s| {
+        let (t0, ns0) = s.points[0];
+        let base = match seq_ns {
+            Some(seq_ns) => seq_ns as f64,
+            None => t0 as f64 * ns0 as f64,
+        };
+        (label(&s.variant), s.points.iter().map(|&(t, ns)| {
+            let speedup = base / ns as f64;
+            (t as f64, if efficiency { speedup / t as f64 } else { speedup })
+        }).collect())
+    }).collect();
+    let max_t = series.iter().flat_map(|s| s.points.iter().map(|&(t, _)| t)).max().unwrap_or(1) as f64;
+    let relative = if seq_ns.is_some() { "" } else { "relative " };
+    if efficiency {
+        line_chart(&format!("{} {}efficiency", workload, relative), "threads", &format!("{}efficiency", relative), &lines, Some(((1.0, 1.0), (max_t, 1.0))))
+    } else {
+        line_chart(&format!("{} {}speedup", workload, relative), "threads", &format!("{}speedup", relative), &lines, Some(((1.0, 1.0), (max_t, max_t))))
+    }
+}
+
+/// Line chart with linear axes starting at 0. `ideal` is drawn as a dashed reference line.
+fn line_chart(title: &str, xlabel: &str, ylabel: &str, lines: &[(&str, Vec<(f64, f64)>)], ideal: Option<((f64, f64), (f64, f64))>) -> String {
+    let all = lines.iter().flat_map(|&(_, ref points)| points.iter().cloned());
+    let (max_x, max_y) = all.fold((1.0f64, 0.0f64), |(mx, my), (x, y)| (mx.max(x), my.max(y)));
+    let max_y = match ideal {
+        Some((_, (_, y))) => max_y.max(y),
+        None => max_y,
+    };
+    let max_y = if max_y > 0.0 { max_y * 1.1 } else { 1.0 };
+    let sx = |x: f64| MARGIN + x / max_x * (WIDTH - 2.0 * MARGIN);
+    let sy = |y: f64| HEIGHT - MARGIN - y / max_y * (HEIGHT - 2.0 * MARGIN);
+
+    let mut svg = svg_header(title);
+    svg.push_str(&format!("<line x1=\"{0}\" y1=\"{1}\" x2=\"{2}\" y2=\"{1}\" stroke=\"black\"/>\n", MARGIN, HEIGHT - MARGIN, WIDTH - MARGIN));
+    svg.push_str(&format!("<line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"black\"/>\n", MARGIN, HEIGHT - MARGIN, MARGIN));
+    for i in 0..6 {
+        let y = max_y * i as f64 / 5.0;
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\" text-anchor=\"end\" font-size=\"11\">{}</text>\n", MARGIN - 6.0, sy(y) + 4.0, tick(y)));
+    }
+    let mut xs: Vec<f64> = lines.iter().flat_map(|&(_, ref points)| points.iter().map(|&(x, _)| x)).collect();
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    xs.dedup();
+    for x in xs {
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"11\">{}</text>\n", sx(x), HEIGHT - MARGIN + 16.0, tick(x)));
+    }
+    svg.push_str(&format!("<text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"12\">{}</text>\n", WIDTH / 2.0, HEIGHT - 15.0, xlabel));
+    svg.push_str(&format!("<text x=\"15\" y=\"{0}\" text-anchor=\"middle\" font-size=\"12\" transform=\"rotate(-90 15 {0})\">{1}</text>\n", HEIGHT / 2.0, ylabel));
+
+    if let Some(((x1, y1), (x2, y2))) = ideal {
+        svg.push_str(&format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"gray\" stroke-dasharray=\"5,5\"/>\n", sx(x1), sy(y1), sx(x2), sy(y2)));
+    }
+    for (i, &(name, ref points)) in lines.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let path: Vec<String> = points.iter().map(|&(x, y)| format!("{:.1},{:.1}", sx(x), sy(y))).collect();
+        svg.push_str(&format!("<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"2\"/>\n", path.join(" "), color));
+        for &(x, y) in points {
+            svg.push_str(&format!("<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"{}\"/>\n", sx(x), sy(y), color));
+        }
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\" font-size=\"11\" fill=\"{}\">{}</text>\n", WIDTH - MARGIN + 5.0, MARGIN + 14.0 * i as f64, color, name));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Threads on the x axis, one row per variant, colored by time relative to the fastest cell.
+fn heatmap(workload: &str, series: &[Series]) -> String {
+    let mut threads: Vec<usize> = series.iter().flat_map(|s| s.points.iter().map(|&(t, _)| t)).collect();
+    threads.sort();
+    threads.dedup();
+    let fastest = series.iter().flat_map(|s| s.points.iter().map(|&(_, ns)| ns)).min().unwrap_or(1).max(1);
+    let cell_w = (WIDTH - 2.0 * MARGIN - 60.0) / threads.len() as f64;
+    let cell_h = (HEIGHT - 2.0 * MARGIN) / series.len() as f64;
+
+    let mut svg = svg_header(&format!("{} time relative to fastest", workload));
+    for (row, s) in series.iter().enumerate() {
+        let y = MARGIN + row as f64 * cell_h;
+        svg.push_str(&format!("<text x=\"{}\" y=\"{}\" text-anchor=\"end\" font-size=\"11\">{}</text>\n", MARGIN + 55.0, y + cell_h / 2.0 + 4.0, label(&s.variant)));
+        for (col, &t) in threads.iter().enumerate() {
+            let x = MARGIN + 60.0 + col as f64 * cell_w;
+            if let Some(&(_, ns)) = s.points.iter().find(|&&(pt, _)| pt == t) {
+                let relative = ns as f64 / fastest as f64;
+                // Green at the fastest, red at 2x or more
+                let heat = ((relative - 1.0).min(1.0) * 255.0) as u8;
+                svg.push_str(&format!("<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"rgb({},{},80)\"/>\n", x, y, cell_w, cell_h, heat, 255 - heat));
+                svg.push_str(&format!("<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-size=\"11\">{:.2}</text>\n", x + cell_w / 2.0, y + cell_h / 2.0 + 4.0, relative));
+            }
+        }
+    }
+    for (col, &t) in threads.iter().enumerate() {
+        svg.push_str(&format!("<text x=\"{:.1}\" y=\"{}\" text-anchor=\"middle\" font-size=\"11\">T{}</text>\n", MARGIN + 60.0 + (col as f64 + 0.5) * cell_w, HEIGHT - MARGIN + 16.0, t));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn svg_header(title: &str) -> String {
+    format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n\
+             <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n\
+             <text x=\"{2}\" y=\"25\" text-anchor=\"middle\" font-size=\"14\">{3}</text>\n", WIDTH, HEIGHT, WIDTH / 2.0, title)
+}
+
+fn tick(v: f64) -> String {
+    if v == v.round() { format!("{}", v) } else { format!("{:.2}", v) }
+}
+
+#[test]
+fn test_parse_implementation() {
+    assert_eq!(Some((4, "".to_string())), parse_implementation("T4"));
+    assert_eq!(Some((8, "size1000".to_string())), parse_implementation("T8_size1000"));
+    assert_eq!(None, parse_implementation("seq"));
+}
@@ -1,10 +1,35 @@
 use criterion::Bencher;
 use forkjoin::{TaskResult,ForkPool,AlgoStyle,ReduceStyle,Algorithm};
 use std::mem;
+use std::slice;
 
+use backend::Join;
 use sortutils::verify_sorted;
+use spawnpool::{self, PoolMode, bench_pool};
+use numa::{self, Policy};
 
-pub fn par_qsort<F>(b: &mut Bencher, threads: usize, size: usize, datafun: F) where
+pub fn par_qsort<F>(b: &mut Bencher, threads: usize, mode: PoolMode, numa: Policy, size: usize, datafun: F) where
+    F: Fn(&mut [usize])
+{
+    let mut data = numa::create_buffer(numa, size, threads, &spawnpool::worker_cpus());
+    let data_ptr = data.as_mut_ptr();
+
+    bench_pool(b, threads, mode, || Algorithm {
+        fun: quicksort_task,
+        style: AlgoStyle::Reduce(ReduceStyle::NoArg(quicksort_join)),
+    }, || {
+        datafun(&mut data[..]);
+    }, |sortpool, ()| {
+        // A reused pool outlives every iteration, so the argument can't be a plain reborrow of data
+        let d = unsafe { slice::from_raw_parts_mut(data_ptr, size) };
+        let job = sortpool.schedule(d);
+        job.recv().unwrap()
+    }, |()| {
+        verify_sorted(unsafe { slice::from_raw_parts(data_ptr, size) });
+    });
+}
+
+pub fn seq_qsort<F>(b: &mut Bencher, size: usize, datafun: F) where
     F: Fn(&mut [usize])
 {
     let mut data: Vec<usize> = (0..size).collect();
@@ -14,13 +39,7 @@ pub fn par_qsort<F>(b: &mut Bencher, threads: usize, size: usize, datafun: F) wh
     b.iter_with_setup_and_verify(|| {
         datafun(&mut data[..]);
     }, |()| {
-        let forkpool = ForkPool::with_threads(threads);
-        let sortpool = forkpool.init_algorithm(Algorithm {
-            fun: quicksort_task,
-            style: AlgoStyle::Reduce(ReduceStyle::NoArg(quicksort_join)),
-        });
-        let job = sortpool.schedule(&mut data_bench[..]);
-        job.recv().unwrap()
+        quicksort_seq(&mut data_bench[..]);
     }, |()| {
         verify_sorted(&mut data_verify[..]);
     });
@@ -29,7 +48,7 @@ pub fn par_qsort<F>(b: &mut Bencher, threads: usize, size: usize, datafun: F) wh
     mem::forget(data_verify);
 }
 
-pub fn seq_qsort<F>(b: &mut Bencher, size: usize, datafun: F) where
+pub fn backend_qsort<J: Join, F>(b: &mut Bencher, join: &J, size: usize, datafun: F) where
     F: Fn(&mut [usize])
 {
     let mut data: Vec<usize> = (0..size).collect();
@@ -39,7 +58,7 @@ pub fn seq_qsort<F>(b: &mut Bencher, size: usize, datafun: F) where
     b.iter_with_setup_and_verify(|| {
         datafun(&mut data[..]);
     }, |()| {
-        quicksort_seq(&mut data_bench[..]);
+        quicksort_backend(join, 0, &mut data_bench[..]);
     }, |()| {
         verify_sorted(&mut data_verify[..]);
     });
@@ -58,7 +77,7 @@ pub fn par_qsort_once(threads: usize, data: &mut [usize]) {
     job.recv().unwrap();
 }
 
-fn quicksort_task(d: &mut [usize]) -> TaskResult<&mut [usize], ()> {
+pub fn quicksort_task(d: &mut [usize]) -> TaskResult<&mut [usize], ()> {
     let len = d.len();
     if len <= 1000 {
         quicksort_seq(d);
@@ -72,7 +91,20 @@ fn quicksort_task(d: &mut [usize]) -> TaskResult<&mut [usize], ()> {
     }
 }
 
-fn quicksort_join(_: &[()]) -> () {}
+fn quicksort_backend<J: Join>(join: &J, depth: usize, d: &mut [usize]) {
+    if d.len() <= 1000 {
+        quicksort_seq(d);
+    } else {
+        let pivot = partition(d);
+        let (low, tmp) = d.split_at_mut(pivot);
+        let (_, high) = tmp.split_at_mut(1);
+
+        let tasks = vec![low, high].into_iter().map(|d| move || quicksort_backend(join, depth + 1, d)).collect();
+        join.join_all(depth, tasks);
+    }
+}
+
+pub fn quicksort_join(_: &[()]) -> () {}
 
 pub fn quicksort_seq(d: &mut [usize]) {
     if d.len() > 1 {
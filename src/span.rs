@@ -0,0 +1,90 @@
+//! Work/span analysis. The task tree of one job is executed sequentially on the calling
+//! thread, timing every task function and join, which gives the total work T1 and the
+//! critical path T∞ of the job.
+
+use forkjoin::{TaskResult, AlgoStyle, ReduceStyle, Algorithm};
+use time;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Analysis {
+    pub tasks: u64,
+    /// T1, the time of all tasks and joins, in ns
+    pub work: u64,
+    /// T∞, the longest chain of tasks and joins depending on each other, in ns
+    pub span: u64,
+}
+
+impl Analysis {
+    /// T1/T∞, the highest speedup any number of threads could reach.
+    pub fn parallelism(&self) -> f64 {
+        self.work as f64 / self.span.max(1) as f64
+    }
+
+    /// The speedup a greedy scheduler is guaranteed on `threads` threads,
+    /// from T_P <= T1/P + T∞.
+    pub fn greedy_speedup(&self, threads: usize) -> f64 {
+        self.work as f64 / (self.work as f64 / threads as f64 + self.span as f64)
+    }
+
+    /// The speedup no scheduler can beat on `threads` threads, min(P, T1/T∞).
+    pub fn max_speedup(&self, threads: usize) -> f64 {
+        self.parallelism().min(threads as f64)
+    }
+}
+
+/// Runs `algorithm` on `arg` sequentially, depth first. Only reduce algorithms are
+/// supported, search algorithms have no well defined work.
+pub fn analyze<Arg, Ret>(algorithm: &Algorithm<Arg, Ret>, arg: Arg) -> Analysis {
+    let overhead = timer_overhead();
+    let (_, analysis) = run(algorithm, arg, overhead);
+    analysis
+}
+
+fn run<Arg, Ret>(algorithm: &Algorithm<Arg, Ret>, arg: Arg, overhead: u64) -> (Ret, Analysis) {
+    let start = time::precise_time_ns();
+    let result = (algorithm.fun)(arg);
+    let task_time = (time::precise_time_ns() - start).saturating_sub(overhead);
+    match result {
+        TaskResult::Done(ret) => (ret, Analysis { tasks: 1, work: task_time, span: task_time }),
+        TaskResult::Fork(args, value) => {
+            let mut values = Vec::with_capacity(args.len());
+            let mut analysis = Analysis { tasks: 1, work: task_time, span: 0 };
+            for arg in args {
+                let (ret, child) = run(algorithm, arg, overhead);
+                values.push(ret);
+                analysis.tasks += child.tasks;
+                analysis.work += child.work;
+                analysis.span = analysis.span.max(child.span);
+            }
+            let start = time::precise_time_ns();
+            let ret = match algorithm.style {
+                AlgoStyle::Reduce(ReduceStyle::NoArg(join)) => join(&values[..]),
+                AlgoStyle::Reduce(ReduceStyle::Arg(join)) => join(value.as_ref().expect("Fork without a value for an Arg join"), &values[..]),
+                AlgoStyle::Search => panic!("Work/span analysis of search algorithms is not supported"),
+            };
+            let join_time = (time::precise_time_ns() - start).saturating_sub(overhead);
+            analysis.work += join_time;
+            analysis.span += task_time + join_time;
+            (ret, analysis)
+        },
+    }
+}
+
+/// Median time of two back to back timer reads, subtracted from every measurement.
+pub fn timer_overhead() -> u64 {
+    let mut times: Vec<u64> = (0..1001).map(|_| {
+        let start = time::precise_time_ns();
+        time::precise_time_ns() - start
+    }).collect();
+    times.sort();
+    times[times.len() / 2]
+}
+
+#[test]
+fn test_bounds() {
+    let analysis = Analysis { tasks: 7, work: 1000, span: 100 };
+    assert_eq!(10.0, analysis.parallelism());
+    assert_eq!(4.0, analysis.max_speedup(4));
+    assert_eq!(10.0, analysis.max_speedup(16));
+    assert_eq!(1000.0 / 350.0, analysis.greedy_speedup(4));
+}
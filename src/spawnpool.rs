@@ -1,6 +1,160 @@
 use criterion::Bencher;
-use forkjoin::{TaskResult,ForkPool,AlgoStyle,ReduceStyle,Algorithm};
+use forkjoin::{TaskResult,ForkPool,AlgoOnPool,AlgoStyle,ReduceStyle,Algorithm};
 use test;
+use time;
+
+use std::cell::RefCell;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+use std::thread;
+
+use pin;
+use threadstats::{self, PoolStats};
+
+/// Whether a parallel benchmark creates its `ForkPool` once and reuses it for
+/// every iteration, or creates and drops a fresh pool inside every timed iteration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PoolMode {
+    Reuse,
+    Fresh,
+}
+
+impl PoolMode {
+    pub fn parse(name: &str) -> PoolMode {
+        match name {
+            "reuse" => PoolMode::Reuse,
+            "fresh" => PoolMode::Fresh,
+            other => panic!("Invalid pool mode: {}", other),
+        }
+    }
+
+    /// Suffix for implementation names. Before pool modes existed the sorts created a pool
+    /// per iteration and the other workloads reused one, so neither mode keeps the plain
+    /// "T<threads>" names, which meant different things for different workloads.
+    pub fn suffix(&self) -> &'static str {
+        match *self {
+            PoolMode::Reuse => "_reuse",
+            PoolMode::Fresh => "_fresh",
+        }
+    }
+}
+
+/// What `bench_pool` recorded on one thread.
+pub struct PoolRuns {
+    /// Time of every iteration, in ns
+    pub times: Vec<u64>,
+    /// What the workers did during the iterations. None without the `threadstats` feature
+    pub threadstats: Option<PoolStats>,
+}
+
+thread_local!(static WORKER_CPUS: RefCell<Vec<usize>> = RefCell::new(Vec::new()));
+
+/// Makes `bench_pool` pin the workers of the pools it creates on this thread to `cpus`.
+pub fn pin_workers(cpus: &[usize]) {
+    WORKER_CPUS.with(|worker_cpus| *worker_cpus.borrow_mut() = cpus.to_vec());
+}
+
+/// The CPUs set with `pin_workers` on this thread.
+pub fn worker_cpus() -> Vec<usize> {
+    WORKER_CPUS.with(|cpus| cpus.borrow().clone())
+}
+
+/// A pool pinned to the CPUs set with `pin_workers`, and the ns spent pinning it, that is
+/// everything but the call to `ForkPool::with_threads`.
+fn pinned_pool<Arg: Send, Ret: Send + Sync>(threads: usize) -> (ForkPool<Arg, Ret>, u64) {
+    WORKER_CPUS.with(|cpus| {
+        let start = time::precise_time_ns();
+        let mut creating = 0;
+        let (forkpool, _) = pin::pinned(&cpus.borrow(), threads, || {
+            let start = time::precise_time_ns();
+            let forkpool = ForkPool::with_threads(threads);
+            creating = time::precise_time_ns() - start;
+            forkpool
+        });
+        (forkpool, time::precise_time_ns() - start - creating)
+    })
+}
+
+thread_local!(static POOL_RUNS: RefCell<PoolRuns> = RefCell::new(PoolRuns {
+    times: Vec::new(),
+    threadstats: None,
+}));
+
+/// Benchmarks `iteration` on a pool running the algorithm `algorithm` returns. With
+/// `PoolMode::Reuse` one pool is created up front and used by every iteration, with
+/// `PoolMode::Fresh` a pool is created and dropped inside every timed iteration.
+/// `setup` and `verify` run outside the timing, like in `iter_with_setup_and_verify`.
+/// The time and worker statistics of every iteration are also recorded for
+/// `take_pool_runs`. A reused pool is snapshotted outside the timing, a fresh one
+/// just before it is dropped. The workers are pinned to the CPUs set with `pin_workers`,
+/// those of a fresh pool inside the timing. The times recorded here leave the pinning
+/// out, criterion's own estimate can't.
+pub fn bench_pool<Arg, Ret, G, S, I, F, O, V>(b: &mut Bencher, threads: usize, mode: PoolMode, algorithm: G, mut setup: S, mut iteration: F, mut verify: V) where
+    Arg: Send,
+    Ret: Send + Sync,
+    G: Fn() -> Algorithm<Arg, Ret>,
+    S: FnMut() -> I,
+    F: FnMut(&AlgoOnPool<Arg, Ret>, I) -> O,
+    V: FnMut(O)
+{
+    match mode {
+        PoolMode::Reuse => {
+            let (forkpool, _) = pinned_pool(threads);
+            let pool = forkpool.init_algorithm(algorithm());
+
+            b.iter_with_setup_and_verify(|| {
+                let input = setup();
+                (input, threadstats::snapshot(&forkpool))
+            }, |(input, before)| {
+                (timed(|| iteration(&pool, input)), before)
+            }, |(output, before)| {
+                if let (Some(before), Some(after)) = (before, threadstats::snapshot(&forkpool)) {
+                    record_threadstats(threadstats::delta(&before, &after, 1));
+                }
+                verify(output)
+            });
+        },
+        PoolMode::Fresh => {
+            b.iter_with_setup_and_verify(setup, |input| {
+                let start = time::precise_time_ns();
+                let (forkpool, pinning) = pinned_pool(threads);
+                let output = {
+                    let pool = forkpool.init_algorithm(algorithm());
+                    iteration(&pool, input)
+                };
+                if let Some(workers) = threadstats::snapshot(&forkpool) {
+                    record_threadstats(PoolStats { workers: workers, jobs: 1 });
+                }
+                drop(forkpool);
+                record_time(time::precise_time_ns() - start - pinning);
+                output
+            }, verify);
+        },
+    }
+}
+
+fn timed<O, F: FnOnce() -> O>(f: F) -> O {
+    let start = time::precise_time_ns();
+    let output = f();
+    record_time(time::precise_time_ns() - start);
+    output
+}
+
+fn record_time(elapsed: u64) {
+    POOL_RUNS.with(|runs| runs.borrow_mut().times.push(elapsed));
+}
+
+fn record_threadstats(stats: PoolStats) {
+    POOL_RUNS.with(|runs| threadstats::accumulate(&mut runs.borrow_mut().threadstats, stats));
+}
+
+/// What `bench_pool` recorded on this thread since the last call.
+pub fn take_pool_runs() -> PoolRuns {
+    POOL_RUNS.with(|runs| mem::replace(&mut *runs.borrow_mut(), PoolRuns {
+        times: Vec::new(),
+        threadstats: None,
+    }))
+}
 
 pub fn spawn(b: &mut Bencher, threads: usize) {
     b.iter_with_setup_and_verify(|| {}, |()| {
@@ -36,3 +190,82 @@ fn void_task(_: usize) -> TaskResult<usize, ()> {
 }
 
 fn void_join(_: &[()]) -> () {}
+
+/// Timestamps, in ns, of the phases in the life of a pool. All relative to
+/// the call to `ForkPool::with_threads`.
+#[derive(Debug, Clone, Copy)]
+pub struct Lifecycle {
+    /// `ForkPool::with_threads` returned
+    pub spawned: u64,
+    /// `init_algorithm` returned
+    pub initialized: u64,
+    /// The first task of the first job started executing on a worker
+    pub first_task: u64,
+    /// One task was running on every worker thread at the same time. None if that
+    /// did not happen within a second
+    pub all_ready: Option<u64>,
+    /// The first `recv()` on the job returned
+    pub first_recv: u64,
+    /// Dropping the pool returned
+    pub dropped: u64,
+}
+
+static FIRST_TASK: AtomicUsize = ATOMIC_USIZE_INIT;
+static ALL_READY: AtomicUsize = ATOMIC_USIZE_INIT;
+static ARRIVED: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Runs one pool from creation to drop and records when each phase completes.
+/// The job forks one task per worker which all wait until every worker has picked
+/// one up, so the result only arrives when all worker threads are up and stealing.
+pub fn lifecycle_once(threads: usize) -> Lifecycle {
+    FIRST_TASK.store(0, Ordering::SeqCst);
+    ALL_READY.store(0, Ordering::SeqCst);
+    ARRIVED.store(0, Ordering::SeqCst);
+
+    let start = time::precise_time_ns();
+    let forkpool: ForkPool<(usize, bool), ()> = ForkPool::with_threads(test::black_box(threads));
+    let spawned = time::precise_time_ns();
+    let (initialized, first_recv) = {
+        let readypool = forkpool.init_algorithm(Algorithm {
+            fun: ready_task,
+            style: AlgoStyle::Reduce(ReduceStyle::NoArg(void_join)),
+        });
+        let initialized = time::precise_time_ns();
+
+        let job = readypool.schedule((threads, true));
+        job.recv().unwrap();
+        (initialized, time::precise_time_ns())
+    };
+    drop(forkpool);
+    let dropped = time::precise_time_ns();
+
+    Lifecycle {
+        spawned: spawned - start,
+        initialized: initialized - start,
+        first_task: (FIRST_TASK.load(Ordering::SeqCst) as u64).saturating_sub(start),
+        all_ready: match ALL_READY.load(Ordering::SeqCst) as u64 {
+            0 => None,
+            ready => Some(ready.saturating_sub(start)),
+        },
+        first_recv: first_recv - start,
+        dropped: dropped - start,
+    }
+}
+
+/// Root forks `workers` children. Every child blocks until all children have started,
+/// or a second has passed, so each of them has to be picked up by a separate worker.
+fn ready_task((workers, root): (usize, bool)) -> TaskResult<(usize, bool), ()> {
+    let now = time::precise_time_ns();
+    if root {
+        FIRST_TASK.store(now as usize, Ordering::SeqCst);
+        TaskResult::Fork((0..workers).map(|_| (workers, false)).collect(), None)
+    } else {
+        if ARRIVED.fetch_add(1, Ordering::SeqCst) + 1 == workers {
+            ALL_READY.store(now as usize, Ordering::SeqCst);
+        }
+        while ARRIVED.load(Ordering::SeqCst) < workers && time::precise_time_ns() - now < 1_000_000_000 {
+            thread::yield_now();
+        }
+        TaskResult::Done(())
+    }
+}
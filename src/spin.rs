@@ -0,0 +1,28 @@
+use test;
+use time;
+
+/// Busy loop doing `iterations` dependent additions. Used to give tasks some
+/// artificial work that the compiler can not remove.
+pub fn spin(iterations: usize) -> usize {
+    let mut x: usize = 0;
+    for i in 0..iterations {
+        x = test::black_box(x.wrapping_add(i));
+    }
+    x
+}
+
+/// Measures how many `spin` iterations this machine does per nanosecond.
+/// Takes the fastest of a few rounds to not be disturbed by other processes.
+pub fn calibrate() -> f64 {
+    let iterations = 10_000_000;
+    let mut best = u64::max_value();
+    for _ in 0..5 {
+        let start = time::precise_time_ns();
+        test::black_box(spin(iterations));
+        let elapsed = time::precise_time_ns() - start;
+        if elapsed < best {
+            best = elapsed;
+        }
+    }
+    iterations as f64 / best as f64
+}
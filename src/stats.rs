@@ -0,0 +1,65 @@
+/// Arithmetic mean of the samples, 0 for no samples.
+pub fn mean(xs: &[u64]) -> f64 {
+    if xs.is_empty() {
+        0.0
+    } else {
+        xs.iter().fold(0.0, |acc, &x| acc + x as f64) / xs.len() as f64
+    }
+}
+
+/// Nearest-rank percentile, `p` in [0, 100]. `sorted` must be sorted ascending and non empty.
+pub fn percentile(sorted: &[u64], p: f64) -> u64 {
+    assert!(!sorted.is_empty());
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    let index = if rank == 0 { 0 } else { rank - 1 };
+    sorted[if index < sorted.len() { index } else { sorted.len() - 1 }]
+}
+
+pub fn median(xs: &[u64]) -> u64 {
+    let mut sorted = xs.to_vec();
+    sorted.sort();
+    percentile(&sorted[..], 50.0)
+}
+
+/// Counts of the values in power of two buckets. Returns (upper bound, count) for
+/// every bucket from the one with the smallest value to the one with the largest.
+pub fn log2_histogram(xs: &[u64]) -> Vec<(u64, usize)> {
+    let bucket = |x: u64| 64 - x.leading_zeros() as usize;
+    let mut counts = [0usize; 65];
+    for &x in xs {
+        counts[bucket(x)] += 1;
+    }
+    let first = counts.iter().position(|&c| c > 0).unwrap_or(0);
+    let last = counts.iter().rposition(|&c| c > 0).unwrap_or(0);
+    (first..last + 1).map(|b| {
+        let upper = if b == 64 { u64::max_value() } else { 1u64 << b };
+        (upper, counts[b])
+    }).collect()
+}
+
+/// Jain's fairness index, 1 when all values are equal and 1/n when one value dominates.
+pub fn jain_fairness(xs: &[f64]) -> f64 {
+    let sum = xs.iter().fold(0.0, |acc, &x| acc + x);
+    let sum_sq = xs.iter().fold(0.0, |acc, &x| acc + x * x);
+    if sum_sq == 0.0 {
+        1.0
+    } else {
+        sum * sum / (xs.len() as f64 * sum_sq)
+    }
+}
+
+#[test]
+fn test_percentile() {
+    let xs: Vec<u64> = (1..101).collect();
+    assert_eq!(1, percentile(&xs[..], 0.0));
+    assert_eq!(50, percentile(&xs[..], 50.0));
+    assert_eq!(99, percentile(&xs[..], 99.0));
+    assert_eq!(100, percentile(&xs[..], 99.9));
+    assert_eq!(100, percentile(&xs[..], 100.0));
+    assert_eq!(3, median(&[5, 1, 3]));
+}
+
+#[test]
+fn test_log2_histogram() {
+    assert_eq!(vec![(8, 1), (16, 0), (32, 2)], log2_histogram(&[5, 20, 31]));
+}
@@ -2,55 +2,180 @@ use criterion::Bencher;
 use forkjoin::{TaskResult,ForkPool,AlgoStyle,ReduceStyle,Algorithm};
 use test;
 
+use std::collections::VecDeque;
+
+use backend::Join;
+use spin::spin;
+use spawnpool::{PoolMode, bench_pool};
+
 pub fn seq_sumtree(b: &mut Bencher, tree: &Tree) {
     b.iter(|| {
         sum_tree_seq(test::black_box(tree))
     });
 }
 
-pub fn par_sumtree(b: &mut Bencher, threads: usize, tree: &Tree) {
+pub fn par_sumtree(b: &mut Bencher, threads: usize, mode: PoolMode, tree: &Tree) {
+    bench_pool(b, threads, mode, || Algorithm {
+        fun: sum_tree_task,
+        style: AlgoStyle::Reduce(ReduceStyle::Arg(sum_tree_join)),
+    }, || {}, |sumpool, ()| {
+        let job = sumpool.schedule(test::black_box(tree));
+        job.recv().unwrap()
+    }, |_| {});
+}
+
+pub fn par_sumtree_once(threads: usize, tree: &Tree) -> usize {
     let forkpool = ForkPool::with_threads(threads);
     let sumpool = forkpool.init_algorithm(Algorithm {
         fun: sum_tree_task,
         style: AlgoStyle::Reduce(ReduceStyle::Arg(sum_tree_join)),
     });
 
+    let job = sumpool.schedule(test::black_box(tree));
+    job.recv().unwrap()
+}
+
+pub fn backend_sumtree<J: Join>(b: &mut Bencher, join: &J, tree: &Tree) {
     b.iter(|| {
-        let job = sumpool.schedule(test::black_box(tree));
+        sum_tree_backend(join, 0, test::black_box(tree))
+    });
+}
+
+pub fn seq_flat_sumtree(b: &mut Bencher, tree: &FlatTree) {
+    b.iter(|| {
+        flat_sum_tree_seq(test::black_box(tree), 0)
+    });
+}
+
+pub fn par_flat_sumtree(b: &mut Bencher, threads: usize, mode: PoolMode, tree: &FlatTree) {
+    bench_pool(b, threads, mode, || Algorithm {
+        fun: flat_sum_tree_task,
+        style: AlgoStyle::Reduce(ReduceStyle::Arg(sum_tree_join)),
+    }, || {}, |sumpool, ()| {
+        let job = sumpool.schedule((test::black_box(tree), 0));
         job.recv().unwrap()
+    }, |_| {});
+}
+
+pub fn par_flat_sumtree_once(threads: usize, tree: &FlatTree) -> usize {
+    let forkpool = ForkPool::with_threads(threads);
+    let sumpool = forkpool.init_algorithm(Algorithm {
+        fun: flat_sum_tree_task,
+        style: AlgoStyle::Reduce(ReduceStyle::Arg(sum_tree_join)),
     });
+
+    let job = sumpool.schedule((test::black_box(tree), 0));
+    job.recv().unwrap()
 }
 
-pub fn par_sumtree_once(threads: usize, tree: &Tree) -> usize {
+pub fn par_sumtree_cutoff(b: &mut Bencher, threads: usize, mode: PoolMode, tree: &Tree, cutoff: Cutoff) {
+    bench_pool(b, threads, mode, || Algorithm {
+        fun: sum_tree_task_cutoff,
+        style: AlgoStyle::Reduce(ReduceStyle::Arg(sum_tree_join)),
+    }, || {}, |sumpool, ()| {
+        let job = sumpool.schedule((test::black_box(tree), 0, cutoff));
+        job.recv().unwrap()
+    }, |_| {});
+}
+
+pub fn par_sumtree_cutoff_once(threads: usize, tree: &Tree, cutoff: Cutoff) -> usize {
     let forkpool = ForkPool::with_threads(threads);
     let sumpool = forkpool.init_algorithm(Algorithm {
-        fun: sum_tree_task,
+        fun: sum_tree_task_cutoff,
         style: AlgoStyle::Reduce(ReduceStyle::Arg(sum_tree_join)),
     });
 
-    let job = sumpool.schedule(test::black_box(tree));
+    let job = sumpool.schedule((test::black_box(tree), 0, cutoff));
     job.recv().unwrap()
 }
 
 #[derive(Debug)]
 pub struct Tree {
     value: usize,
+    /// Number of `spin` iterations to perform when visiting this node
+    work: usize,
+    /// Number of nodes in the subtree rooted at this node, including itself
+    size: usize,
     children: Vec<Tree>,
 }
+impl Tree {
+    fn new(children: Vec<Tree>) -> Tree {
+        let size = children.iter().fold(1, |acc, c| acc + c.size);
+        Tree {
+            value: 1,
+            work: 0,
+            size: size,
+            children: children,
+        }
+    }
+
+    pub fn value(&self) -> usize {
+        self.value
+    }
+
+    pub fn children(&self) -> &[Tree] {
+        &self.children[..]
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
 impl Clone for Tree {
     fn clone(&self) -> Tree {
         Tree {
             value: self.value,
+            work: self.work,
+            size: self.size,
             children: self.children.iter().map(|t| t.clone()).collect(),
         }
     }
 }
 
-fn sum_tree_seq(t: &Tree) -> usize {
+/// When `sum_tree_task_cutoff` stops forking and sums the rest of the subtree sequentially.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cutoff {
+    /// Subtrees with at most this many nodes
+    Size(usize),
+    /// Nodes at this depth or deeper, the root being at depth 0
+    Depth(usize),
+}
+
+impl Cutoff {
+    /// Parses `size:<nodes>` or `depth:<depth>`.
+    pub fn parse(spec: &str) -> Cutoff {
+        let parts: Vec<&str> = spec.splitn(2, ':').collect();
+        let value = parts.get(1).and_then(|v| v.parse().ok());
+        match (parts[0], value) {
+            ("size", Some(v)) => Cutoff::Size(v),
+            ("depth", Some(v)) => Cutoff::Depth(v),
+            _ => panic!("Invalid sumtree cutoff: {}", spec),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match *self {
+            Cutoff::Size(s) => format!("size{}", s),
+            Cutoff::Depth(d) => format!("depth{}", d),
+        }
+    }
+}
+
+/// Sets the per node work of every node in the tree to `spins` iterations.
+pub fn set_work(t: &mut Tree, spins: usize) {
+    t.work = spins;
+    for c in t.children.iter_mut() {
+        set_work(c, spins);
+    }
+}
+
+pub fn sum_tree_seq(t: &Tree) -> usize {
+    spin(t.work);
     t.value + t.children.iter().fold(0, |acc, t2| acc + sum_tree_seq(t2))
 }
 
-fn sum_tree_task(t: &Tree) -> TaskResult<&Tree, usize> {
+pub fn sum_tree_task(t: &Tree) -> TaskResult<&Tree, usize> {
+    spin(t.work);
     if t.children.is_empty() {
         TaskResult::Done(t.value)
     } else {
@@ -62,7 +187,31 @@ fn sum_tree_task(t: &Tree) -> TaskResult<&Tree, usize> {
     }
 }
 
-fn sum_tree_join(value: &usize, values: &[usize]) -> usize {
+fn sum_tree_backend<J: Join>(join: &J, depth: usize, t: &Tree) -> usize {
+    spin(t.work);
+    if t.children.is_empty() {
+        t.value
+    } else {
+        let tasks = t.children.iter().map(|c| move || sum_tree_backend(join, depth + 1, c)).collect();
+        sum_tree_join(&t.value, &join.join_all(depth, tasks)[..])
+    }
+}
+
+pub fn sum_tree_task_cutoff((t, depth, cutoff): (&Tree, usize, Cutoff)) -> TaskResult<(&Tree, usize, Cutoff), usize> {
+    let sequential = match cutoff {
+        Cutoff::Size(size) => t.size <= size,
+        Cutoff::Depth(max_depth) => depth >= max_depth,
+    };
+    if sequential || t.children.is_empty() {
+        TaskResult::Done(sum_tree_seq(t))
+    } else {
+        spin(t.work);
+        let fork_args: Vec<(&Tree, usize, Cutoff)> = t.children.iter().map(|c| (c, depth + 1, cutoff)).collect();
+        TaskResult::Fork(fork_args, Some(t.value))
+    }
+}
+
+pub fn sum_tree_join(value: &usize, values: &[usize]) -> usize {
     *value + values.iter().fold(0, |acc, &v| acc + v)
 }
 
@@ -72,22 +221,13 @@ pub fn gen_unbalanced_tree(depth: usize) -> Tree {
     for i in 0..depth {
         children.push(gen_unbalanced_tree(i));
     }
-    Tree {
-        value: 1,
-        children: children,
-    }
+    Tree::new(children)
 }
 
 pub fn gen_list_tree(depth: usize) -> Tree {
-    let mut tree = Tree {
-        value: 1,
-        children: vec![],
-    };
+    let mut tree = Tree::new(vec![]);
     for _ in 0..depth {
-        tree = Tree {
-            value: 1,
-            children: vec![tree],
-        };
+        tree = Tree::new(vec![tree]);
     }
     tree
 }
@@ -99,8 +239,142 @@ pub fn gen_balanced_tree(depth: usize) -> Tree {
             children.push(gen_balanced_tree(depth-1));
         }
     }
-    Tree {
-        value: 1,
-        children: children,
+    Tree::new(children)
+}
+
+#[test]
+fn test_tree_sizes() {
+    assert_eq!(1 << 8, gen_unbalanced_tree(8).size);
+    assert_eq!(9, gen_list_tree(8).size);
+    assert_eq!((1 << 9) - 1, gen_balanced_tree(8).size);
+    let tree = gen_unbalanced_tree(6);
+    assert_eq!(sum_tree_seq(&tree), tree.size);
+}
+
+/// How the nodes of a tree are laid out in memory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// Every node allocated separately, as built by the generators.
+    Nested,
+    /// All nodes in one `Vec`, in breadth first order.
+    Bfs,
+    /// All nodes in one `Vec`, in depth first pre-order.
+    Dfs,
+}
+
+impl Layout {
+    pub fn parse(name: &str) -> Layout {
+        match name {
+            "nested" => Layout::Nested,
+            "bfs" => Layout::Bfs,
+            "dfs" => Layout::Dfs,
+            other => panic!("Invalid tree layout: {}", other),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Layout::Nested => "nested",
+            Layout::Bfs => "bfs",
+            Layout::Dfs => "dfs",
+        }
+    }
+}
+
+/// Arena representation of a `Tree`. Nodes refer to their children through
+/// a range in `edges`, which holds indices into `nodes`. The root is at index 0.
+#[derive(Debug, Clone)]
+pub struct FlatTree {
+    nodes: Vec<FlatNode>,
+    edges: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct FlatNode {
+    value: usize,
+    work: usize,
+    first_child: usize,
+    num_children: usize,
+}
+
+impl FlatTree {
+    pub fn from_tree(tree: &Tree, layout: Layout) -> FlatTree {
+        let mut flat = FlatTree {
+            nodes: vec![],
+            edges: vec![],
+        };
+        match layout {
+            Layout::Bfs => flat.push_bfs(tree),
+            Layout::Dfs => { flat.push_dfs(tree); },
+            Layout::Nested => panic!("A FlatTree can't have nested layout"),
+        }
+        flat
+    }
+
+    fn push_bfs(&mut self, tree: &Tree) {
+        let mut queue = VecDeque::new();
+        queue.push_back(tree);
+        let mut next_index = 1;
+        while let Some(t) = queue.pop_front() {
+            self.nodes.push(FlatNode {
+                value: t.value,
+                work: t.work,
+                first_child: self.edges.len(),
+                num_children: t.children.len(),
+            });
+            for c in t.children.iter() {
+                self.edges.push(next_index);
+                next_index += 1;
+                queue.push_back(c);
+            }
+        }
+    }
+
+    fn push_dfs(&mut self, tree: &Tree) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(FlatNode {
+            value: tree.value,
+            work: tree.work,
+            first_child: 0,
+            num_children: tree.children.len(),
+        });
+        let children: Vec<usize> = tree.children.iter().map(|c| self.push_dfs(c)).collect();
+        self.nodes[index].first_child = self.edges.len();
+        self.edges.extend_from_slice(&children[..]);
+        index
+    }
+
+    fn children(&self, index: usize) -> &[usize] {
+        let node = &self.nodes[index];
+        &self.edges[node.first_child..node.first_child + node.num_children]
+    }
+}
+
+fn flat_sum_tree_seq(t: &FlatTree, index: usize) -> usize {
+    let node = &t.nodes[index];
+    spin(node.work);
+    node.value + t.children(index).iter().fold(0, |acc, &c| acc + flat_sum_tree_seq(t, c))
+}
+
+fn flat_sum_tree_task((t, index): (&FlatTree, usize)) -> TaskResult<(&FlatTree, usize), usize> {
+    let node = &t.nodes[index];
+    spin(node.work);
+    if node.num_children == 0 {
+        TaskResult::Done(node.value)
+    } else {
+        let fork_args: Vec<(&FlatTree, usize)> = t.children(index).iter().map(|&c| (t, c)).collect();
+        TaskResult::Fork(fork_args, Some(node.value))
+    }
+}
+
+#[test]
+fn test_flat_tree() {
+    for tree in vec![gen_unbalanced_tree(8), gen_list_tree(8), gen_balanced_tree(8)] {
+        let expected = sum_tree_seq(&tree);
+        for &layout in [Layout::Bfs, Layout::Dfs].iter() {
+            let flat = FlatTree::from_tree(&tree, layout);
+            assert_eq!(expected, flat.nodes.len());
+            assert_eq!(expected, flat_sum_tree_seq(&flat, 0));
+        }
     }
 }
@@ -0,0 +1,124 @@
+//! Per worker statistics of a pool, as counted by forkjoin when built with its
+//! `threadstats` feature. Without the feature `snapshot` returns None.
+
+use forkjoin::ForkPool;
+
+/// What one worker did, over the lifetime of its pool in a snapshot.
+#[derive(Debug, Clone, Copy)]
+pub struct WorkerStats {
+    pub tasks: u64,
+    pub steals: u64,
+    pub failed_steals: u64,
+    /// Time spent sleeping because there was nothing to steal, in us
+    pub idle_us: u64,
+}
+
+/// What the workers of one thread count did during `jobs` jobs.
+#[derive(Debug, Clone)]
+pub struct PoolStats {
+    pub workers: Vec<WorkerStats>,
+    pub jobs: usize,
+}
+
+impl PoolStats {
+    fn per_job<F: Fn(&WorkerStats) -> u64>(&self, field: F) -> f64 {
+        self.workers.iter().fold(0, |acc, w| acc + field(w)) as f64 / self.jobs as f64
+    }
+
+    pub fn tasks(&self) -> f64 {
+        self.per_job(|w| w.tasks)
+    }
+
+    pub fn steals(&self) -> f64 {
+        self.per_job(|w| w.steals)
+    }
+
+    pub fn failed_steals(&self) -> f64 {
+        self.per_job(|w| w.failed_steals)
+    }
+
+    pub fn idle_us(&self) -> f64 {
+        self.per_job(|w| w.idle_us)
+    }
+
+    /// Share of all tasks executed by the busiest worker, 1/workers when perfectly balanced.
+    pub fn busiest_share(&self) -> f64 {
+        let total = self.workers.iter().fold(0, |acc, w| acc + w.tasks);
+        let busiest = self.workers.iter().map(|w| w.tasks).max().unwrap_or(0);
+        if total == 0 { 0.0 } else { busiest as f64 / total as f64 }
+    }
+}
+
+/// The totals of every worker of `pool` so far.
+#[cfg(feature = "threadstats")]
+pub fn snapshot<Arg: Send, Ret: Send + Sync>(pool: &ForkPool<Arg, Ret>) -> Option<Vec<WorkerStats>> {
+    Some(pool.thread_stats().iter().map(|s| WorkerStats {
+        tasks: s.exec_tasks as u64,
+        steals: s.steals as u64,
+        failed_steals: s.steal_fails as u64,
+        idle_us: s.sleep_us as u64,
+    }).collect())
+}
+
+#[cfg(not(feature = "threadstats"))]
+pub fn snapshot<Arg: Send, Ret: Send + Sync>(_pool: &ForkPool<Arg, Ret>) -> Option<Vec<WorkerStats>> {
+    None
+}
+
+/// What the workers did between two snapshots of the same pool, counted as `jobs` jobs.
+pub fn delta(before: &[WorkerStats], after: &[WorkerStats], jobs: usize) -> PoolStats {
+    PoolStats {
+        workers: before.iter().zip(after.iter()).map(|(b, a)| WorkerStats {
+            tasks: a.tasks - b.tasks,
+            steals: a.steals - b.steals,
+            failed_steals: a.failed_steals - b.failed_steals,
+            idle_us: a.idle_us - b.idle_us,
+        }).collect(),
+        jobs: jobs,
+    }
+}
+
+/// Adds `stats` to `total`, worker by worker. The first stats added become the total.
+pub fn accumulate(total: &mut Option<PoolStats>, stats: PoolStats) {
+    if let Some(ref mut sum) = *total {
+        for (s, w) in sum.workers.iter_mut().zip(stats.workers.iter()) {
+            s.tasks += w.tasks;
+            s.steals += w.steals;
+            s.failed_steals += w.failed_steals;
+            s.idle_us += w.idle_us;
+        }
+        sum.jobs += stats.jobs;
+        return;
+    }
+    *total = Some(stats);
+}
+
+#[test]
+fn test_pool_stats() {
+    let stats = PoolStats {
+        workers: vec![
+            WorkerStats { tasks: 30, steals: 2, failed_steals: 10, idle_us: 100 },
+            WorkerStats { tasks: 10, steals: 4, failed_steals: 20, idle_us: 300 },
+        ],
+        jobs: 2,
+    };
+    assert_eq!(20.0, stats.tasks());
+    assert_eq!(3.0, stats.steals());
+    assert_eq!(200.0, stats.idle_us());
+    assert_eq!(0.75, stats.busiest_share());
+
+    let after = vec![
+        WorkerStats { tasks: 45, steals: 3, failed_steals: 10, idle_us: 150 },
+        WorkerStats { tasks: 15, steals: 4, failed_steals: 25, idle_us: 300 },
+    ];
+    let job = delta(&stats.workers, &after, 1);
+    assert_eq!(20.0, job.tasks());
+    assert_eq!(50.0, job.idle_us());
+
+    let mut total = None;
+    accumulate(&mut total, stats.clone());
+    accumulate(&mut total, job);
+    let total = total.unwrap();
+    assert_eq!(3, total.jobs);
+    assert_eq!(20.0, total.tasks());
+}
@@ -0,0 +1,125 @@
+use forkjoin::{ForkPool,AlgoStyle,ReduceStyle,Algorithm};
+use thread_scoped;
+use time;
+
+use std::collections::VecDeque;
+use std::slice;
+use std::sync::Mutex;
+
+use fib::{FIB, fib};
+use quicksort::{quicksort_task, quicksort_join};
+use sortutils::{create_vec_rnd, verify_sorted};
+
+pub struct Throughput {
+    pub jobs: usize,
+    pub elapsed_ns: u64,
+    /// Latency of every job, from `schedule` until `recv` returned, in ns
+    pub latencies: Vec<u64>,
+}
+
+/// Lets client threads share things that are only used under a lock or that
+/// every client only touches disjoint parts of.
+pub struct Shared<T>(pub T);
+unsafe impl<T> Sync for Shared<T> {}
+
+pub fn fib_throughput(threads: usize, clients: usize, inflight: usize, jobs: usize, n: usize) -> Throughput {
+    let forkpool = ForkPool::with_threads(threads);
+    let fibpool = Shared(forkpool.init_algorithm(FIB));
+    let lock = Mutex::new(());
+    let expected = fib(n);
+
+    run_clients(clients, inflight, jobs, |_| {}, |_| {
+        let _guard = lock.lock().unwrap();
+        fibpool.0.schedule(n)
+    }, |job| {
+        assert_eq!(expected, job.recv().unwrap());
+    })
+}
+
+pub fn qsort_throughput(threads: usize, clients: usize, inflight: usize, jobs: usize, size: usize) -> Throughput {
+    let forkpool = ForkPool::with_threads(threads);
+    let sortpool = Shared(forkpool.init_algorithm(Algorithm {
+        fun: quicksort_task,
+        style: AlgoStyle::Reduce(ReduceStyle::NoArg(quicksort_join)),
+    }));
+    let lock = Mutex::new(());
+
+    // One buffer per job that can be in flight at the same time
+    let mut buffers: Vec<Vec<usize>> = (0..clients * inflight).map(|_| vec![0; size]).collect();
+    let buffers_ptr = Shared(buffers.as_mut_ptr());
+
+    let slot_buffer = |slot: usize| unsafe {
+        let buffer = &mut *buffers_ptr.0.offset(slot as isize);
+        slice::from_raw_parts_mut(buffer.as_mut_ptr(), buffer.len())
+    };
+
+    let throughput = run_clients(clients, inflight, jobs, |slot| {
+        create_vec_rnd(893475343, slot_buffer(slot));
+    }, |slot| {
+        let _guard = lock.lock().unwrap();
+        sortpool.0.schedule(slot_buffer(slot))
+    }, |job| {
+        job.recv().unwrap();
+    });
+
+    for buffer in buffers.iter() {
+        verify_sorted(&buffer[..]);
+    }
+    throughput
+}
+
+/// Runs `clients` threads that together submit `jobs` jobs through `schedule` and
+/// wait for them with `wait`. Every client keeps up to `inflight` jobs outstanding
+/// and waits for them in submission order, so with `inflight > 1` a latency also
+/// includes the time a finished job waited behind an earlier one.
+/// `prepare` and `schedule` get a slot number unique among the jobs in flight across
+/// all clients. `prepare` fills the slot before the job's latency starts counting.
+fn run_clients<P, S, W, J>(clients: usize, inflight: usize, jobs: usize, prepare: P, schedule: S, wait: W) -> Throughput where
+    P: Fn(usize) + Sync,
+    S: Fn(usize) -> J + Sync,
+    W: Fn(J) + Sync,
+{
+    assert!(clients > 0 && inflight > 0, "Need at least one client and one job in flight");
+    let prepare = &prepare;
+    let schedule = &schedule;
+    let wait = &wait;
+
+    let start = time::precise_time_ns();
+    let guards: Vec<_> = (0..clients).map(|client| {
+        let client_jobs = jobs / clients + if client < jobs % clients { 1 } else { 0 };
+        unsafe {
+            thread_scoped::scoped(move || {
+                let mut latencies = Vec::with_capacity(client_jobs);
+                let mut outstanding = VecDeque::with_capacity(inflight);
+                for i in 0..client_jobs {
+                    if outstanding.len() == inflight {
+                        let (scheduled, job) = outstanding.pop_front().unwrap();
+                        wait(job);
+                        latencies.push(time::precise_time_ns() - scheduled);
+                    }
+                    let slot = client * inflight + i % inflight;
+                    prepare(slot);
+                    let scheduled = time::precise_time_ns();
+                    outstanding.push_back((scheduled, schedule(slot)));
+                }
+                while let Some((scheduled, job)) = outstanding.pop_front() {
+                    wait(job);
+                    latencies.push(time::precise_time_ns() - scheduled);
+                }
+                latencies
+            })
+        }
+    }).collect();
+
+    let mut latencies = Vec::with_capacity(jobs);
+    for guard in guards {
+        latencies.extend(guard.join());
+    }
+    let elapsed_ns = time::precise_time_ns() - start;
+
+    Throughput {
+        jobs: jobs,
+        elapsed_ns: elapsed_ns,
+        latencies: latencies,
+    }
+}
@@ -0,0 +1,173 @@
+//! Execution timelines of single jobs. The traced versions of the task and join functions
+//! record when they ran on which thread into a buffer per thread, and `write_chrome` exports
+//! everything recorded as Chrome trace_event JSON, viewable in chrome://tracing or
+//! Perfetto. Recording costs an uncontended lock per task, so traces show the shape of
+//! a run rather than its exact speed.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, Write};
+use std::ptr::Unique;
+use std::path::Path;
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+
+use forkjoin::TaskResult;
+use time;
+
+use fib::{fib_task, fib_join};
+use manifest::json_string;
+use mergesort::{mergesort_task, mergesort_join};
+use pin;
+use quicksort::{quicksort_task, quicksort_join};
+use sumtree::{Tree, sum_tree_task, sum_tree_join};
+
+/// One execution of a task function, in ns.
+#[derive(Debug, Clone, Copy)]
+pub struct Event {
+    pub name: &'static str,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Everything one thread recorded.
+#[derive(Debug, Clone)]
+pub struct Thread {
+    /// OS thread id, as in the pinning of the workers
+    pub tid: i32,
+    pub events: Vec<Event>,
+}
+
+type Buffer = Arc<Mutex<Thread>>;
+
+static INIT: Once = ONCE_INIT;
+static mut BUFFERS: *const Mutex<Vec<Buffer>> = 0 as *const Mutex<Vec<Buffer>>;
+
+/// The buffers of every thread that recorded anything, in order of their first event.
+fn buffers() -> &'static Mutex<Vec<Buffer>> {
+    unsafe {
+        INIT.call_once(|| {
+            BUFFERS = Box::into_raw(Box::new(Mutex::new(vec![])));
+        });
+        &*BUFFERS
+    }
+}
+
+thread_local!(static BUFFER: RefCell<Option<Buffer>> = RefCell::new(None));
+
+fn record(name: &'static str, start: u64) {
+    let end = time::precise_time_ns();
+    BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        if buffer.is_none() {
+            let new: Buffer = Arc::new(Mutex::new(Thread {
+                tid: pin::current_thread_id(),
+                events: vec![],
+            }));
+            buffers().lock().unwrap().push(new.clone());
+            *buffer = Some(new);
+        }
+        buffer.as_ref().unwrap().lock().unwrap().events.push(Event {
+            name: name,
+            start: start,
+            end: end,
+        });
+    });
+}
+
+/// Removes everything recorded so far, per thread that recorded anything.
+pub fn take() -> Vec<Thread> {
+    let buffers = buffers().lock().unwrap();
+    buffers.iter().map(|b| {
+        let mut thread = b.lock().unwrap();
+        let taken = thread.clone();
+        thread.events.clear();
+        taken
+    }).filter(|thread| !thread.events.is_empty()).collect()
+}
+
+/// Complete ("X") events with timestamps in us relative to the first event, on the OS
+/// thread ids.
+pub fn chrome_json(threads: &[Thread]) -> String {
+    let origin = threads.iter().flat_map(|thread| thread.events.iter().map(|e| e.start)).min().unwrap_or(0);
+    let mut events: Vec<String> = vec![];
+    for thread in threads {
+        events.push(format!("{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":{},\"args\":{{\"name\":\"thread {}\"}}}}", thread.tid, thread.tid));
+        for e in thread.events.iter() {
+            events.push(format!("{{\"name\":{},\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{:.3},\"dur\":{:.3}}}",
+                json_string(e.name), thread.tid, (e.start - origin) as f64 / 1000.0, (e.end - e.start) as f64 / 1000.0));
+        }
+    }
+    format!("{{\"traceEvents\":[\n{}\n],\"displayTimeUnit\":\"ns\"}}\n", events.join(",\n"))
+}
+
+pub fn write_chrome(path: &Path, threads: &[Thread]) -> io::Result<()> {
+    let mut file = try!(File::create(path));
+    file.write_all(chrome_json(threads).as_bytes())
+}
+
+pub fn traced_fib_task(n: usize) -> TaskResult<usize, usize> {
+    let start = time::precise_time_ns();
+    let result = fib_task(n);
+    record("fib_task", start);
+    result
+}
+
+pub fn traced_fib_join(values: &[usize]) -> usize {
+    let start = time::precise_time_ns();
+    let result = fib_join(values);
+    record("fib_join", start);
+    result
+}
+
+pub fn traced_quicksort_task(d: &mut [usize]) -> TaskResult<&mut [usize], ()> {
+    let start = time::precise_time_ns();
+    let result = quicksort_task(d);
+    record("quicksort_task", start);
+    result
+}
+
+pub fn traced_quicksort_join(values: &[()]) -> () {
+    let start = time::precise_time_ns();
+    let result = quicksort_join(values);
+    record("quicksort_join", start);
+    result
+}
+
+pub fn traced_mergesort_task(d: &mut [usize]) -> TaskResult<&mut [usize], (Unique<usize>, usize)> {
+    let start = time::precise_time_ns();
+    let result = mergesort_task(d);
+    record("mergesort_task", start);
+    result
+}
+
+pub fn traced_mergesort_join(xs: &[(Unique<usize>, usize)]) -> (Unique<usize>, usize) {
+    let start = time::precise_time_ns();
+    let result = mergesort_join(xs);
+    record("mergesort_join", start);
+    result
+}
+
+pub fn traced_sum_tree_task(t: &Tree) -> TaskResult<&Tree, usize> {
+    let start = time::precise_time_ns();
+    let result = sum_tree_task(t);
+    record("sum_tree_task", start);
+    result
+}
+
+pub fn traced_sum_tree_join(value: &usize, values: &[usize]) -> usize {
+    let start = time::precise_time_ns();
+    let result = sum_tree_join(value, values);
+    record("sum_tree_join", start);
+    result
+}
+
+#[test]
+fn test_chrome_json() {
+    let threads = vec![
+        Thread { tid: 4100, events: vec![Event { name: "fib_task", start: 1000, end: 3500 }] },
+        Thread { tid: 4101, events: vec![Event { name: "fib_join", start: 2000, end: 2500 }] },
+    ];
+    let json = chrome_json(&threads);
+    assert!(json.contains("{\"name\":\"fib_join\",\"ph\":\"X\",\"pid\":1,\"tid\":4101,\"ts\":1.000,\"dur\":0.500}"));
+    assert!(json.starts_with("{\"traceEvents\":["));
+}
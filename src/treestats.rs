@@ -0,0 +1,87 @@
+//! Shape of the task tree of one job, found by calling the task function on every
+//! forked argument sequentially without joining anything.
+
+use std::collections::BTreeMap;
+
+use forkjoin::TaskResult;
+use time;
+
+use span::timer_overhead;
+
+pub struct TreeStats {
+    pub tasks: u64,
+    /// Number of tasks at every depth, the root being at depth 0
+    pub depths: Vec<u64>,
+    /// Number of tasks forking every number of children. Tasks returning Done and
+    /// tasks forking nothing both have fan-out 0.
+    pub fanouts: BTreeMap<usize, u64>,
+    /// Time of every task with fan-out 0, in ns
+    pub leaf_times: Vec<u64>,
+}
+
+impl TreeStats {
+    pub fn leaves(&self) -> u64 {
+        self.fanouts.get(&0).cloned().unwrap_or(0)
+    }
+
+    /// Average number of children of the tasks that fork any.
+    pub fn mean_fanout(&self) -> f64 {
+        let (inner, children) = self.fanouts.iter().filter(|&(&f, _)| f > 0)
+            .fold((0, 0), |(inner, children), (&f, &count)| (inner + count, children + f as u64 * count));
+        if inner == 0 { 0.0 } else { children as f64 / inner as f64 }
+    }
+}
+
+/// Walks the tree depth first with an explicit stack, so deep trees like the list
+/// shaped sumtree do not overflow the thread's stack.
+pub fn walk<Arg, Ret>(fun: fn(Arg) -> TaskResult<Arg, Ret>, arg: Arg) -> TreeStats {
+    let overhead = timer_overhead();
+    let mut stats = TreeStats {
+        tasks: 0,
+        depths: vec![],
+        fanouts: BTreeMap::new(),
+        leaf_times: vec![],
+    };
+    let mut stack = vec![(arg, 0)];
+    while let Some((arg, depth)) = stack.pop() {
+        let start = time::precise_time_ns();
+        let result = fun(arg);
+        let elapsed = (time::precise_time_ns() - start).saturating_sub(overhead);
+
+        stats.tasks += 1;
+        if stats.depths.len() <= depth {
+            stats.depths.resize(depth + 1, 0);
+        }
+        stats.depths[depth] += 1;
+        let fanout = match result {
+            TaskResult::Done(_) => 0,
+            TaskResult::Fork(args, _) => {
+                let fanout = args.len();
+                // Reversed so the first child is walked first, like a worker popping its own deque
+                for arg in args.into_iter().rev() {
+                    stack.push((arg, depth + 1));
+                }
+                fanout
+            },
+        };
+        *stats.fanouts.entry(fanout).or_insert(0) += 1;
+        if fanout == 0 {
+            stats.leaf_times.push(elapsed);
+        }
+    }
+    stats.leaf_times.sort();
+    stats
+}
+
+#[test]
+fn test_walk() {
+    fn fib_task(n: usize) -> TaskResult<usize, usize> {
+        if n < 2 { TaskResult::Done(1) } else { TaskResult::Fork(vec![n - 1, n - 2], None) }
+    }
+    let stats = walk(fib_task, 4);
+    assert_eq!(9, stats.tasks);
+    assert_eq!(vec![1, 2, 4, 2], stats.depths);
+    assert_eq!(5, stats.leaves());
+    assert_eq!(2.0, stats.mean_fanout());
+    assert_eq!(5, stats.leaf_times.len());
+}
@@ -0,0 +1,171 @@
+//! Unbalanced Tree Search (UTS). The tree is never materialized, each node only carries
+//! the SHA-1 state it was spawned with and its number of children is derived from it.
+
+use criterion::Bencher;
+use forkjoin::{TaskResult,ForkPool,AlgoStyle,ReduceStyle,Algorithm};
+use test;
+
+use sha1::sha1;
+use spawnpool::{PoolMode, bench_pool};
+
+pub fn seq_uts(b: &mut Bencher, params: &'static UtsParams) {
+    let expected = uts_seq(&UtsNode::root(params));
+
+    b.iter_with_setup_and_verify(|| {}, |()| {
+        uts_seq(&UtsNode::root(test::black_box(params)))
+    }, |nodes| {
+        assert_eq!(expected, nodes);
+    });
+}
+
+pub fn par_uts(b: &mut Bencher, threads: usize, mode: PoolMode, params: &'static UtsParams) {
+    let expected = uts_seq(&UtsNode::root(params));
+
+    bench_pool(b, threads, mode, || UTS, || {}, |utspool, ()| {
+        let job = utspool.schedule(UtsNode::root(test::black_box(params)));
+        job.recv().unwrap()
+    }, |nodes| {
+        assert_eq!(expected, nodes);
+    });
+}
+
+pub fn par_uts_once(threads: usize, params: &'static UtsParams) -> usize {
+    let forkpool = ForkPool::with_threads(threads);
+    let utspool = forkpool.init_algorithm(UTS);
+
+    let job = utspool.schedule(UtsNode::root(test::black_box(params)));
+    job.recv().unwrap()
+}
+
+#[derive(Debug)]
+pub enum TreeShape {
+    /// Root has `b0` children, every other node has `m` children with probability `q`.
+    Binomial { b0: usize, q: f64, m: usize },
+    /// Geometric number of children with expected value `b0`, cut off at depth `gen_mx`.
+    Geometric { b0: f64, gen_mx: usize },
+}
+
+#[derive(Debug)]
+pub struct UtsParams {
+    pub name: &'static str,
+    pub shape: TreeShape,
+    pub root_seed: u32,
+}
+
+/// Geometric, fixed shape. 4 130 071 nodes, depth 10
+pub static T1: UtsParams = UtsParams {
+    name: "T1",
+    shape: TreeShape::Geometric { b0: 4.0, gen_mx: 10 },
+    root_seed: 19,
+};
+
+/// Binomial. 4 112 897 nodes, depth 1572
+pub static T3: UtsParams = UtsParams {
+    name: "T3",
+    shape: TreeShape::Binomial { b0: 2000, q: 0.124875, m: 8 },
+    root_seed: 42,
+};
+
+pub fn uts_params(name: &str) -> &'static UtsParams {
+    match name {
+        "T1" => &T1,
+        "T3" => &T3,
+        other => panic!("Invalid UTS tree: {}", other),
+    }
+}
+
+const UTS: Algorithm<UtsNode, usize> = Algorithm {
+    fun: uts_task,
+    style: AlgoStyle::Reduce(ReduceStyle::Arg(uts_join)),
+};
+
+/// Same limit as the reference implementation, only a binomial root may exceed it.
+const MAX_CHILDREN: usize = 100;
+
+#[derive(Clone)]
+pub struct UtsNode {
+    params: &'static UtsParams,
+    state: [u8; 20],
+    height: usize,
+}
+
+impl UtsNode {
+    pub fn root(params: &'static UtsParams) -> UtsNode {
+        let seed = params.root_seed;
+        let mut buf = [0u8; 20];
+        buf[16] = (seed >> 24) as u8;
+        buf[17] = (seed >> 16) as u8;
+        buf[18] = (seed >> 8) as u8;
+        buf[19] = seed as u8;
+        UtsNode {
+            params: params,
+            state: sha1(&buf),
+            height: 0,
+        }
+    }
+
+    fn child(&self, i: usize) -> UtsNode {
+        let mut buf = [0u8; 24];
+        for (b, s) in buf.iter_mut().zip(self.state.iter()) {
+            *b = *s;
+        }
+        buf[20] = (i >> 24) as u8;
+        buf[21] = (i >> 16) as u8;
+        buf[22] = (i >> 8) as u8;
+        buf[23] = i as u8;
+        UtsNode {
+            params: self.params,
+            state: sha1(&buf),
+            height: self.height + 1,
+        }
+    }
+
+    /// Uniform number in [0, 1) taken from the last four bytes of the state.
+    fn prob(&self) -> f64 {
+        let s = &self.state;
+        let r = ((s[16] as u32) << 24 | (s[17] as u32) << 16 | (s[18] as u32) << 8 | s[19] as u32) & 0x7fffffff;
+        r as f64 / 2147483648.0
+    }
+
+    fn num_children(&self) -> usize {
+        match self.params.shape {
+            TreeShape::Binomial { b0, q, m } => {
+                if self.height == 0 {
+                    b0
+                } else if self.prob() < q {
+                    m
+                } else {
+                    0
+                }
+            },
+            TreeShape::Geometric { b0, gen_mx } => {
+                let b = if self.height < gen_mx { b0 } else { 0.0 };
+                let p = 1.0 / (1.0 + b);
+                let n = ((1.0 - self.prob()).ln() / (1.0 - p).ln()).floor() as usize;
+                if n > MAX_CHILDREN { MAX_CHILDREN } else { n }
+            },
+        }
+    }
+
+    fn children(&self) -> Vec<UtsNode> {
+        (0..self.num_children()).map(|i| self.child(i)).collect()
+    }
+}
+
+/// Sequential reference count of the nodes in the tree rooted at `n`.
+pub fn uts_seq(n: &UtsNode) -> usize {
+    n.children().iter().fold(1, |acc, c| acc + uts_seq(c))
+}
+
+fn uts_task(n: UtsNode) -> TaskResult<UtsNode, usize> {
+    let children = n.children();
+    if children.is_empty() {
+        TaskResult::Done(1)
+    } else {
+        TaskResult::Fork(children, Some(1))
+    }
+}
+
+fn uts_join(value: &usize, values: &[usize]) -> usize {
+    *value + values.iter().fold(0, |acc, &v| acc + v)
+}
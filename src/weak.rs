@@ -0,0 +1,77 @@
+/// How a workload grows its input with the thread count in weak scaling mode,
+/// so that every thread count gets about the same amount of work per thread.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scaling {
+    /// Input multiplied by T, for work linear in the argument
+    Linear,
+    /// Argument increased by log2(T), for work that doubles with every step
+    Log2,
+    /// Argument increased by log_phi(T), for fib
+    Fib,
+}
+
+impl Scaling {
+    pub fn scale(&self, arg: usize, threads: usize) -> usize {
+        let t = threads as f64;
+        match *self {
+            Scaling::Linear => arg * threads,
+            Scaling::Log2 => arg + t.log2().round() as usize,
+            Scaling::Fib => arg + (t.ln() / 1.618033988749895f64.ln()).round() as usize,
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match *self {
+            Scaling::Linear => "argument x T",
+            Scaling::Log2 => "argument + log2(T)",
+            Scaling::Fib => "argument + log_phi(T)",
+        }
+    }
+}
+
+/// The result of one thread count in a weak scaling sweep.
+pub struct WeakPoint {
+    pub threads: usize,
+    /// The scaled argument
+    pub arg: usize,
+    /// Amount of work in the workload's own unit (fib calls, n*log2(n), nodes)
+    pub units: u64,
+    /// Median time of one job, in ns
+    pub elapsed: u64,
+}
+
+impl WeakPoint {
+    /// Constant under perfect weak scaling.
+    pub fn ns_per_unit_per_thread(&self) -> f64 {
+        self.elapsed as f64 * self.threads as f64 / self.units as f64
+    }
+}
+
+/// Number of calls the naive recursive fib makes for `n`.
+pub fn fib_calls(n: usize) -> u64 {
+    let (mut a, mut b) = (1u64, 1u64);
+    for _ in 0..n {
+        let c = a + b + 1;
+        a = b;
+        b = c;
+    }
+    a
+}
+
+/// Work of sorting `n` elements, n*log2(n), since the sorts are not linear in their input.
+pub fn sort_units(n: usize) -> u64 {
+    if n < 2 {
+        return n as u64;
+    }
+    (n as f64 * (n as f64).log2()).round() as u64
+}
+
+#[test]
+fn test_scale() {
+    assert_eq!(80000, Scaling::Linear.scale(20000, 4));
+    assert_eq!(14, Scaling::Log2.scale(12, 4));
+    assert_eq!(34, Scaling::Fib.scale(31, 4));
+    assert_eq!(1, fib_calls(1));
+    assert_eq!(9, fib_calls(4));
+    assert_eq!(10240, sort_units(1024));
+}